@@ -0,0 +1,22 @@
+// ,---------------------------------------------------------------------------
+// | Meshes shared by the benchmarks
+// '---------------------------------------------------------------------------
+
+use edge_breaker::Mesh;
+
+// Grid of n x n quads in the z = 0 plane, each split along the same diagonal
+pub fn grid(n: usize) -> Mesh {
+    let mut mesh = Mesh::default();
+    for i in 0..(n + 1) * (n + 1) {
+        mesh.vertices
+            .push([(i % (n + 1)) as f32, (i / (n + 1)) as f32, 0.0]);
+    }
+    for j in 0..n {
+        for i in 0..n {
+            let a = j * (n + 1) + i;
+            mesh.faces
+                .extend([[a, a + 1, a + n + 2], [a, a + n + 2, a + n + 1]]);
+        }
+    }
+    mesh
+}
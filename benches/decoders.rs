@@ -0,0 +1,38 @@
+// ,---------------------------------------------------------------------------
+// | Decoders: forward, Spirale Reversi and Wrap&Zip on the same history
+// '---------------------------------------------------------------------------
+
+mod common;
+
+use common::grid;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use edge_breaker::{DecoderKind, DecompressOptions, compress, decompress_with};
+use std::hint::black_box;
+
+fn decoders(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decoders");
+    group.sample_size(10);
+    for n in [100, 500] {
+        let mesh = grid(n);
+        let compressed = compress(&mesh).expect("grid compresses");
+        for decoder in [
+            DecoderKind::Forward,
+            DecoderKind::SpiraleReversi,
+            DecoderKind::WrapZip,
+        ] {
+            let options = DecompressOptions {
+                decoder,
+                ..Default::default()
+            };
+            group.bench_with_input(
+                BenchmarkId::new(decoder.name(), mesh.faces.len()),
+                &compressed,
+                |b, compressed| b.iter(|| decompress_with(black_box(compressed), &options)),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decoders);
+criterion_main!(benches);
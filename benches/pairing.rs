@@ -0,0 +1,63 @@
+// ,---------------------------------------------------------------------------
+// | Pairing: sort-based opposite half-edges against the original hash maps
+// '---------------------------------------------------------------------------
+
+mod common;
+
+use common::grid;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use edge_breaker::edgebreaker::manifold::pair;
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    hint::black_box,
+};
+
+// Opposites as the original `HalfEdges::init` found them, with one map for
+// the half-edges and one for the conflicting edges
+fn pair_hashed(faces: &[[usize; 3]]) -> (Vec<Option<usize>>, usize) {
+    let mut opposite = vec![None; faces.len() * 3];
+    let mut edge_map: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut conflicts: HashMap<(usize, usize), usize> = HashMap::new();
+    for (t, face) in faces.iter().enumerate() {
+        for i in 0..3 {
+            let h = 3 * t + i;
+            let (a, b) = (face[i], face[(i + 1) % 3]);
+            if let Some(&g) = edge_map.get(&(b, a)) {
+                if opposite[g].is_some() {
+                    *conflicts.entry((a, b)).or_insert(0) += 1;
+                } else {
+                    opposite[g] = Some(h);
+                    opposite[h] = Some(g);
+                }
+            } else if let Entry::Vacant(entry) = edge_map.entry((a, b)) {
+                entry.insert(h);
+            } else {
+                *conflicts.entry((a, b)).or_insert(0) += 1;
+            }
+        }
+    }
+    (opposite, conflicts.values().sum())
+}
+
+fn pairing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pairing");
+    group.sample_size(10);
+    for n in [100, 1000] {
+        let mesh = grid(n);
+        let (faces, vertex_count) = (mesh.faces, mesh.vertices.len());
+        group.bench_with_input(
+            BenchmarkId::new("hashed", faces.len()),
+            &faces,
+            |b, faces| b.iter(|| pair_hashed(black_box(faces))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("sorted", faces.len()),
+            &faces,
+            |b, faces| b.iter(|| pair(black_box(faces), vertex_count)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pairing);
+criterion_main!(benches);
@@ -0,0 +1,567 @@
+// ,---------------------------------------------------------------------------
+// | Binary container
+// '---------------------------------------------------------------------------
+//
+// Layout (integers are LEB128 varints unless noted):
+//
+//   magic    "EBRK"
+//   version  u8
+//   flags    u8, bits 0-1 hold the history encoding, bit 2 is set when the
+//            geometry is quantized, bits 3 and 4 when texture coordinates and
+//            normals are present, bit 5 when vertex properties are present,
+//            bit 6 when faces are flipped after decoding, bit 7 when faces
+//            are merged into polygons
+//   more     u8, bit 0 is set when a component directory is present, bit 1
+//            when welded vertices are put back after decoding
+//   vertices count
+//   dir      component count, then per component its face count, number
+//            of vertex labels, history bit offset, first table entry, first
+//            duplicate entry, first vertex and vertex count, followed by the
+//            byte offsets of its quantized geometry, texture coordinate
+//            corners, normal corners and diagonal bits, with the number of
+//            values used before it after each attribute offset
+//   history  op count, byte length, bytes. With a directory arithmetic coding
+//            starts over on a byte boundary for every component.
+//   table    byte length, adaptive Elias-gamma coded entries
+//   dup      pair count, pairs as (position delta, index)
+//   geometry vertex count * 3 little endian f32, or if quantized:
+//            bits u8, bounding box as 6 little endian f32, byte length, bytes
+//   texcoords value count, values as little endian f32, byte length, bytes
+//   normals  same as texcoords
+//   props    property count, then per property the name length, name, type
+//            id and one little endian value of that type per vertex
+//   flipped  face count, face indices as deltas
+//   welded   corner count, pairs as (corner delta, vertex)
+//   polygons byte length, diagonal bits
+
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{
+    EdgeBreakerError, ScalarKind, VertexProperty,
+    edgebreaker::public::{
+        Attribute, ComponentEntry, Compressed, HistoryEncoding, QuantizedGeometry, Table,
+    },
+};
+
+pub const MAGIC: &[u8; 4] = b"EBRK";
+const VERSION: u8 = 4;
+
+const ENCODING_MASK: u8 = 0b11;
+const QUANTIZED: u8 = 0b100;
+const TEXCOORDS: u8 = 0b1000;
+const NORMALS: u8 = 0b10000;
+const PROPERTIES: u8 = 0b100000;
+const FLIPPED: u8 = 0b1000000;
+const POLYGONS: u8 = 0b10000000;
+
+const DIRECTORY: u8 = 0b1;
+const WELDED: u8 = 0b10;
+
+/// Check whether `reader` starts with the container magic without consuming it.
+pub fn is_binary<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    Ok(reader.fill_buf()?.starts_with(MAGIC))
+}
+
+impl Compressed {
+    pub fn write_binary<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        let mut flags = encoding_id(self.encoding);
+        if self.geometry.is_some() {
+            flags |= QUANTIZED;
+        }
+        if self.texcoords.is_some() {
+            flags |= TEXCOORDS;
+        }
+        if self.normals.is_some() {
+            flags |= NORMALS;
+        }
+        if !self.properties.is_empty() {
+            flags |= PROPERTIES;
+        }
+        if !self.flipped.is_empty() {
+            flags |= FLIPPED;
+        }
+        if self.polygons.is_some() {
+            flags |= POLYGONS;
+        }
+        let mut more = 0;
+        if !self.components.is_empty() {
+            more |= DIRECTORY;
+        }
+        if !self.welded.is_empty() {
+            more |= WELDED;
+        }
+        writer.write_all(&[VERSION, flags, more])?;
+        write_varint(writer, self.vertex_count())?;
+
+        let (history, offsets) = self
+            .encoding
+            .encode_components(&self.history, &self.components);
+        if !self.components.is_empty() {
+            write_varint(writer, self.components.len())?;
+            for (c, offset) in self.components.iter().zip(offsets) {
+                for value in [
+                    c.faces,
+                    c.vertices,
+                    offset,
+                    c.table,
+                    c.dup,
+                    c.vertex_range.start,
+                    c.vertex_range.len(),
+                    c.geometry,
+                    c.texcoords.0,
+                    c.texcoords.1,
+                    c.normals.0,
+                    c.normals.1,
+                    c.polygons,
+                ] {
+                    write_varint(writer, value)?;
+                }
+            }
+        }
+
+        write_varint(writer, self.history.len())?;
+        write_varint(writer, history.len())?;
+        writer.write_all(&history)?;
+
+        let table = Table::encode(&self.table);
+        write_varint(writer, table.len())?;
+        writer.write_all(&table)?;
+
+        write_varint(writer, self.dup.len())?;
+        let mut last = 0;
+        for &(pos, idx) in &self.dup {
+            write_varint(writer, pos - last)?;
+            write_varint(writer, idx)?;
+            last = pos;
+        }
+
+        if let Some(geometry) = &self.geometry {
+            writer.write_all(&[geometry.bits])?;
+            for c in geometry.min.iter().chain(&geometry.max) {
+                writer.write_all(&c.to_le_bytes())?;
+            }
+            write_varint(writer, geometry.data.len())?;
+            writer.write_all(&geometry.data)?;
+        } else {
+            for v in &self.vertices {
+                for c in v {
+                    writer.write_all(&c.to_le_bytes())?;
+                }
+            }
+        }
+
+        if let Some(texcoords) = &self.texcoords {
+            write_attribute(writer, texcoords)?;
+        }
+        if let Some(normals) = &self.normals {
+            write_attribute(writer, normals)?;
+        }
+
+        if !self.properties.is_empty() {
+            write_varint(writer, self.properties.len())?;
+            for p in &self.properties {
+                write_varint(writer, p.name.len())?;
+                writer.write_all(p.name.as_bytes())?;
+                let id = ScalarKind::ALL.iter().position(|&k| k == p.kind).unwrap();
+                writer.write_all(&[id as u8])?;
+                for &v in &p.values {
+                    writer.write_all(&p.kind.to_le_bytes(v))?;
+                }
+            }
+        }
+
+        if !self.flipped.is_empty() {
+            write_varint(writer, self.flipped.len())?;
+            let mut last = 0;
+            for &f in &self.flipped {
+                write_varint(writer, f - last)?;
+                last = f;
+            }
+        }
+
+        if !self.welded.is_empty() {
+            write_varint(writer, self.welded.len())?;
+            let mut last = 0;
+            for &(c, v) in &self.welded {
+                write_varint(writer, c - last)?;
+                write_varint(writer, v)?;
+                last = c;
+            }
+        }
+
+        if let Some(polygons) = &self.polygons {
+            write_varint(writer, polygons.len())?;
+            writer.write_all(polygons)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_binary<R: Read>(reader: &mut R) -> Result<Self, EdgeBreakerError> {
+        let mut header = [0; 7];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(EdgeBreakerError::MalformedInput(
+                "missing container magic".to_string(),
+            ));
+        }
+        if header[4] != VERSION {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "unsupported container version {}",
+                header[4]
+            )));
+        }
+        let encoding = match header[5] & ENCODING_MASK {
+            0 => HistoryEncoding::Prefix,
+            1 => HistoryEncoding::Arithmetic,
+            2 => HistoryEncoding::Conditioned,
+            id => {
+                return Err(EdgeBreakerError::MalformedInput(format!(
+                    "unknown history encoding {id}"
+                )));
+            }
+        };
+        let vertex_count = read_varint(reader)?;
+
+        let mut components = Vec::new();
+        if header[6] & DIRECTORY != 0 {
+            for _ in 0..read_varint(reader)? {
+                let mut entry = ComponentEntry {
+                    faces: read_varint(reader)?,
+                    vertices: read_varint(reader)?,
+                    history_bit: read_varint(reader)?,
+                    table: read_varint(reader)?,
+                    dup: read_varint(reader)?,
+                    vertex_range: 0..0,
+                    geometry: 0,
+                    texcoords: (0, 0),
+                    normals: (0, 0),
+                    polygons: 0,
+                };
+                let first = read_varint(reader)?;
+                let end = first.checked_add(read_varint(reader)?).ok_or_else(|| {
+                    EdgeBreakerError::MalformedInput(format!(
+                        "vertices of component {} overflow",
+                        components.len()
+                    ))
+                })?;
+                entry.vertex_range = first..end;
+                entry.geometry = read_varint(reader)?;
+                entry.texcoords = (read_varint(reader)?, read_varint(reader)?);
+                entry.normals = (read_varint(reader)?, read_varint(reader)?);
+                entry.polygons = read_varint(reader)?;
+                components.push(entry);
+            }
+        }
+
+        let op_count = read_varint(reader)?;
+        let byte_count = read_varint(reader)?;
+        let bytes = read_bytes(reader, byte_count)?;
+        let history = if components.is_empty() {
+            encoding.decode(&bytes, op_count)?
+        } else {
+            encoding.decode_components(&bytes, &components)?
+        };
+        let byte_count = read_varint(reader)?;
+        let table = Table::decode(&read_bytes(reader, byte_count)?, &history)?;
+
+        let mut dup = Vec::new();
+        let mut last = 0;
+        for _ in 0..read_varint(reader)? {
+            last = read_delta(reader, last)?;
+            dup.push((last, read_varint(reader)?));
+        }
+
+        let mut vertices = Vec::new();
+        let mut geometry = None;
+        if header[5] & QUANTIZED != 0 {
+            let mut bits = [0];
+            reader.read_exact(&mut bits)?;
+            let min = [read_f32(reader)?, read_f32(reader)?, read_f32(reader)?];
+            let max = [read_f32(reader)?, read_f32(reader)?, read_f32(reader)?];
+            let byte_count = read_varint(reader)?;
+            geometry = Some(QuantizedGeometry {
+                bits: bits[0],
+                min,
+                max,
+                count: vertex_count,
+                data: read_bytes(reader, byte_count)?,
+            });
+        } else {
+            for _ in 0..vertex_count {
+                vertices.push([read_f32(reader)?, read_f32(reader)?, read_f32(reader)?]);
+            }
+        }
+
+        let texcoords = match header[5] & TEXCOORDS {
+            0 => None,
+            _ => Some(read_attribute(reader)?),
+        };
+        let normals = match header[5] & NORMALS {
+            0 => None,
+            _ => Some(read_attribute(reader)?),
+        };
+
+        let mut properties = Vec::new();
+        if header[5] & PROPERTIES != 0 {
+            for _ in 0..read_varint(reader)? {
+                let name_len = read_varint(reader)?;
+                let name = String::from_utf8(read_bytes(reader, name_len)?).map_err(|_| {
+                    EdgeBreakerError::MalformedInput("property name is not UTF-8".to_string())
+                })?;
+                let mut id = [0];
+                reader.read_exact(&mut id)?;
+                let kind = *ScalarKind::ALL.get(id[0] as usize).ok_or_else(|| {
+                    EdgeBreakerError::MalformedInput(format!("unknown property type {}", id[0]))
+                })?;
+                let size = vertex_count.checked_mul(kind.size()).ok_or_else(|| {
+                    EdgeBreakerError::MalformedInput(format!(
+                        "property '{name}' of {vertex_count} vertices overflows"
+                    ))
+                })?;
+                let bytes = read_bytes(reader, size)?;
+                properties.push(VertexProperty {
+                    name,
+                    kind,
+                    values: bytes
+                        .chunks(kind.size())
+                        .map(|b| kind.from_le_bytes(b))
+                        .collect(),
+                });
+            }
+        }
+
+        let mut flipped = Vec::new();
+        if header[5] & FLIPPED != 0 {
+            let mut last = 0;
+            for _ in 0..read_varint(reader)? {
+                last = read_delta(reader, last)?;
+                flipped.push(last);
+            }
+        }
+
+        let mut welded = Vec::new();
+        if header[6] & WELDED != 0 {
+            let mut last = 0;
+            for _ in 0..read_varint(reader)? {
+                last = read_delta(reader, last)?;
+                welded.push((last, read_varint(reader)?));
+            }
+        }
+
+        let polygons = match header[5] & POLYGONS {
+            0 => None,
+            _ => {
+                let byte_count = read_varint(reader)?;
+                Some(read_bytes(reader, byte_count)?)
+            }
+        };
+
+        Ok(Compressed {
+            vertices,
+            history,
+            table,
+            dup,
+            encoding,
+            geometry,
+            texcoords,
+            normals,
+            properties,
+            flipped,
+            welded,
+            polygons,
+            components,
+        })
+    }
+}
+
+fn encoding_id(encoding: HistoryEncoding) -> u8 {
+    match encoding {
+        HistoryEncoding::Prefix => 0,
+        HistoryEncoding::Arithmetic => 1,
+        HistoryEncoding::Conditioned => 2,
+    }
+}
+
+fn write_attribute<W: Write, const N: usize>(
+    writer: &mut W,
+    attribute: &Attribute<N>,
+) -> io::Result<()> {
+    write_varint(writer, attribute.values.len())?;
+    for c in attribute.values.iter().flatten() {
+        writer.write_all(&c.to_le_bytes())?;
+    }
+    write_varint(writer, attribute.corners.len())?;
+    writer.write_all(&attribute.corners)
+}
+
+fn read_attribute<R: Read, const N: usize>(
+    reader: &mut R,
+) -> Result<Attribute<N>, EdgeBreakerError> {
+    let mut values = Vec::new();
+    for _ in 0..read_varint(reader)? {
+        let mut value = [0.0; N];
+        for c in value.iter_mut() {
+            *c = read_f32(reader)?;
+        }
+        values.push(value);
+    }
+    let byte_count = read_varint(reader)?;
+    Ok(Attribute {
+        values,
+        corners: read_bytes(reader, byte_count)?,
+    })
+}
+
+// Read a position coded as the difference to the previous one
+fn read_delta<R: Read>(reader: &mut R, last: usize) -> Result<usize, EdgeBreakerError> {
+    last.checked_add(read_varint(reader)?)
+        .ok_or_else(|| EdgeBreakerError::MalformedInput(format!("position after {last} overflows")))
+}
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+fn read_bytes<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(count as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != count {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(bytes)
+}
+
+pub fn write_varint<W: Write>(writer: &mut W, mut value: usize) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+pub fn read_varint<R: Read>(reader: &mut R) -> Result<usize, EdgeBreakerError> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let mut byte = [0];
+        reader.read_exact(&mut byte)?;
+        // The last byte may only set the bits that are left
+        let bits = (byte[0] & 0x7f) as usize;
+        if shift >= usize::BITS || (shift + 7 > usize::BITS && bits >> (usize::BITS - shift) != 0) {
+            return Err(EdgeBreakerError::MalformedInput(
+                "varint does not fit into usize".to_string(),
+            ));
+        }
+        value |= bits << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressOptions, Mesh, compress_with, decompress};
+
+    #[test]
+    fn binary_roundtrip() {
+        // 3x3 grid of quads with the middle one missing, and a triangle
+        let mut mesh = Mesh::default();
+        for i in 0..19 {
+            mesh.vertices.push([(i % 4) as f32, (i / 4) as f32, 0.5]);
+        }
+        mesh.faces.push([16, 17, 18]);
+        for j in 0..3 {
+            for i in 0..3 {
+                if (i, j) != (1, 1) {
+                    let a = j * 4 + i;
+                    mesh.faces.push([a, a + 1, a + 5]);
+                    mesh.faces.push([a, a + 5, a + 4]);
+                }
+            }
+        }
+
+        for (encoding, threads, quantization) in [
+            (HistoryEncoding::Prefix, 0, None),
+            (HistoryEncoding::Arithmetic, 2, None),
+            (HistoryEncoding::Conditioned, 2, None),
+            (HistoryEncoding::Arithmetic, 2, Some(12)),
+        ] {
+            let options = CompressOptions {
+                encoding,
+                threads,
+                quantization,
+                ..Default::default()
+            };
+            let compressed = compress_with(&mesh, &options).unwrap();
+            let mut bytes = Vec::new();
+            compressed.write_binary(&mut bytes).unwrap();
+            assert!(is_binary(&mut &bytes[..]).unwrap());
+
+            let read = Compressed::read_binary(&mut &bytes[..]).unwrap();
+            assert_eq!(read.vertices, compressed.vertices);
+            assert_eq!(read.table, compressed.table);
+            assert!(!read.table.is_empty());
+            assert_eq!(read.encoding, encoding);
+            assert_eq!(read.components, compressed.components);
+            assert_eq!(read.components.is_empty(), threads == 0);
+            assert_eq!(decompress(&read).unwrap(), decompress(&compressed).unwrap());
+        }
+    }
+
+    #[test]
+    fn overflowing_sizes_are_rejected() {
+        let header = |flags: u8, more: u8, vertices: usize| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend([VERSION, flags, more]);
+            write_varint(&mut bytes, vertices).unwrap();
+            bytes
+        };
+        let malformed = |bytes: Vec<u8>| {
+            matches!(
+                Compressed::read_binary(&mut &bytes[..]),
+                Err(EdgeBreakerError::MalformedInput(msg)) if msg.contains("overflow")
+            )
+        };
+
+        // Component whose vertex range ends past usize::MAX
+        let mut bytes = header(0, DIRECTORY, 0);
+        for value in [1, 0, 0, 0, 0, 0, usize::MAX, 1] {
+            write_varint(&mut bytes, value).unwrap();
+        }
+        assert!(malformed(bytes));
+
+        // Property of 8 byte values for usize::MAX quantized vertices
+        let mut bytes = header(QUANTIZED | PROPERTIES, 0, usize::MAX);
+        bytes.extend([0, 0, 0, 0, 12]);
+        bytes.extend([0; 24]);
+        bytes.extend([0, 1, 1, b'p', 7]);
+        assert!(malformed(bytes));
+    }
+
+    #[test]
+    fn overlong_varints_are_rejected() {
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, usize::MAX).unwrap();
+        assert_eq!(read_varint(&mut &bytes[..]).unwrap(), usize::MAX);
+
+        // Bits above the 64th in the tenth byte, and an eleventh byte
+        for last in [[0x02].as_slice(), &[0x7f], &[0x81, 0x00]] {
+            let mut bytes = vec![0xff; 9];
+            bytes.extend(last);
+            assert!(matches!(
+                read_varint(&mut &bytes[..]),
+                Err(EdgeBreakerError::MalformedInput(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// ,---------------------------------------------------------------------------
+// | Check: what kind of mesh the compressor is given
+// '---------------------------------------------------------------------------
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use crate::{Mesh, edgebreaker::orientation::orient};
+
+/// Connected component of faces that share vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component {
+    pub faces: usize,
+    pub vertices: usize,
+    pub boundary_loops: usize,
+    /// Euler characteristic, vertices - edges + faces
+    pub euler: i64,
+    pub manifold: bool,
+    pub orientable: bool,
+    /// Number of handles, or of cross-caps if the component is not
+    /// orientable. Only known for manifold components.
+    pub genus: Option<usize>,
+}
+
+/// Problems and topology of a mesh. Degenerate faces and faces with indices
+/// out of range are left out of everything that follows them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Check {
+    pub vertices: usize,
+    pub faces: usize,
+    /// Faces that use a vertex more than once
+    pub degenerate: Vec<usize>,
+    /// Faces that reference a vertex that does not exist
+    pub out_of_range: Vec<usize>,
+    /// Vertices no face references
+    pub unreferenced: Vec<usize>,
+    /// Edges shared by more than two faces
+    pub non_manifold_edges: Vec<[usize; 2]>,
+    /// Vertices where more than one fan of faces meets
+    pub non_manifold_vertices: Vec<usize>,
+    pub boundary_loops: usize,
+    pub components: Vec<Component>,
+}
+
+impl Check {
+    pub fn is_manifold(&self) -> bool {
+        self.non_manifold_edges.is_empty() && self.non_manifold_vertices.is_empty()
+    }
+
+    pub fn is_orientable(&self) -> bool {
+        self.components.iter().all(|c| c.orientable)
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let numbers = |values: &[usize]| list(values.iter().map(usize::to_string).collect());
+
+        let mut json = String::new();
+        let _ = write!(
+            json,
+            "{{\"vertices\":{},\"faces\":{},\"degenerate\":{},\"out_of_range\":{},\
+             \"unreferenced\":{},\"non_manifold_edges\":{},\"non_manifold_vertices\":{},\
+             \"boundary_loops\":{},\"manifold\":{},\"orientable\":{},\"components\":{}}}",
+            self.vertices,
+            self.faces,
+            numbers(&self.degenerate),
+            numbers(&self.out_of_range),
+            numbers(&self.unreferenced),
+            list(self.non_manifold_edges.iter().map(|e| numbers(e)).collect()),
+            numbers(&self.non_manifold_vertices),
+            self.boundary_loops,
+            self.is_manifold(),
+            self.is_orientable(),
+            list(
+                self.components
+                    .iter()
+                    .map(|c| format!(
+                        "{{\"faces\":{},\"vertices\":{},\"boundary_loops\":{},\"euler\":{},\
+                         \"manifold\":{},\"orientable\":{},\"genus\":{}}}",
+                        c.faces,
+                        c.vertices,
+                        c.boundary_loops,
+                        c.euler,
+                        c.manifold,
+                        c.orientable,
+                        c.genus.map_or("null".to_string(), |g| g.to_string())
+                    ))
+                    .collect()
+            )
+        );
+        json
+    }
+}
+
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+fn union(parent: &mut [usize], x: usize, y: usize) {
+    let (x, y) = (find(parent, x), find(parent, y));
+    parent[y] = x;
+}
+
+pub fn check(mesh: &Mesh) -> Check {
+    let n = mesh.vertices.len();
+    let mut report = Check {
+        vertices: n,
+        faces: mesh.faces.len(),
+        ..Default::default()
+    };
+
+    let mut faces = Vec::new();
+    for (i, f) in mesh.faces.iter().enumerate() {
+        if f.iter().any(|&v| v >= n) {
+            report.out_of_range.push(i);
+        } else if f[0] == f[1] || f[1] == f[2] || f[2] == f[0] {
+            report.degenerate.push(i);
+        } else {
+            faces.push(*f);
+        }
+    }
+
+    let mut referenced = vec![false; n];
+    for &v in faces.iter().flatten() {
+        referenced[v] = true;
+    }
+    report.unreferenced = (0..n).filter(|&v| !referenced[v]).collect();
+
+    // Half-edge h runs from corner h % 3 of face h / 3 to the next corner
+    let next = |h: usize| h - h % 3 + (h + 1) % 3;
+    let start = |h: usize| faces[h / 3][h % 3];
+    let mut edges: HashMap<[usize; 2], Vec<usize>> = HashMap::new();
+    for h in 0..faces.len() * 3 {
+        let (a, b) = (start(h), start(next(h)));
+        edges.entry([a.min(b), a.max(b)]).or_default().push(h);
+    }
+
+    // Corners around a vertex form a fan when joined by edges with two
+    // faces, whatever their winding
+    let mut fans = (0..faces.len() * 3).collect::<Vec<_>>();
+    let mut vertex_parent = (0..n).collect::<Vec<_>>();
+    let mut boundary_parent = (0..n).collect::<Vec<_>>();
+    let mut on_boundary = vec![false; n];
+    for (&[a, b], hs) in &edges {
+        union(&mut vertex_parent, a, b);
+        match hs[..] {
+            [_] => {
+                union(&mut boundary_parent, a, b);
+                on_boundary[a] = true;
+                on_boundary[b] = true;
+            }
+            [h, g] if start(h) == start(g) => {
+                union(&mut fans, h, g);
+                union(&mut fans, next(h), next(g));
+            }
+            [h, g] => {
+                union(&mut fans, h, next(g));
+                union(&mut fans, next(h), g);
+            }
+            _ => report.non_manifold_edges.push([a, b]),
+        }
+    }
+    report.non_manifold_edges.sort();
+
+    let mut fan_of = vec![None; n];
+    let mut non_manifold = vec![false; n];
+    for c in 0..faces.len() * 3 {
+        let root = find(&mut fans, c);
+        let v = start(c);
+        if *fan_of[v].get_or_insert(root) != root {
+            non_manifold[v] = true;
+        }
+    }
+    report.non_manifold_vertices = (0..n).filter(|&v| non_manifold[v]).collect();
+
+    let loops = (0..n)
+        .filter(|&v| on_boundary[v] && find(&mut boundary_parent, v) == v)
+        .collect::<Vec<_>>();
+    report.boundary_loops = loops.len();
+
+    // Faces that still disagree with a neighbour after orienting them
+    let flipped = orient(&faces);
+    let mut conflicting = vec![false; faces.len()];
+    for hs in edges.values() {
+        if let [h, g] = hs[..]
+            && (start(h) == start(g)) == (flipped[h / 3] == flipped[g / 3])
+        {
+            conflicting[h / 3] = true;
+        }
+    }
+
+    let mut index = HashMap::new();
+    for v in (0..n).filter(|&v| referenced[v]) {
+        let root = find(&mut vertex_parent, v);
+        let i = *index.entry(root).or_insert(report.components.len());
+        if i == report.components.len() {
+            report.components.push(Component {
+                faces: 0,
+                vertices: 0,
+                boundary_loops: 0,
+                euler: 0,
+                manifold: true,
+                orientable: true,
+                genus: None,
+            });
+        }
+        let component = &mut report.components[i];
+        component.vertices += 1;
+        component.euler += 1;
+        component.manifold &= !non_manifold[v];
+    }
+    for (f, face) in faces.iter().enumerate() {
+        let component = &mut report.components[index[&find(&mut vertex_parent, face[0])]];
+        component.faces += 1;
+        component.euler += 1;
+        component.orientable &= !conflicting[f];
+    }
+    for &[a, _] in edges.keys() {
+        report.components[index[&find(&mut vertex_parent, a)]].euler -= 1;
+    }
+    for &[a, _] in &report.non_manifold_edges {
+        report.components[index[&find(&mut vertex_parent, a)]].manifold = false;
+    }
+    for v in loops {
+        report.components[index[&find(&mut vertex_parent, v)]].boundary_loops += 1;
+    }
+
+    // Euler characteristic is 2 - 2g - b, or 2 - g - b without orientation
+    for c in report.components.iter_mut() {
+        let missing = 2 - c.boundary_loops as i64 - c.euler;
+        c.genus = match (c.manifold, c.orientable) {
+            (false, _) => None,
+            (true, true) => Some((missing / 2) as usize),
+            (true, false) => Some(missing as usize),
+        };
+    }
+
+    report
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "vertices: {}", self.vertices)?;
+        writeln!(f, "faces: {}", self.faces)?;
+        for (name, items) in [
+            ("degenerate faces", &self.degenerate),
+            ("faces out of range", &self.out_of_range),
+            ("unreferenced vertices", &self.unreferenced),
+            ("non-manifold vertices", &self.non_manifold_vertices),
+        ] {
+            write!(f, "{name}: {}", items.len())?;
+            for item in items.iter().take(10) {
+                write!(f, " {item}")?;
+            }
+            if items.len() > 10 {
+                write!(f, " ...")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "non-manifold edges: {}", self.non_manifold_edges.len())?;
+        for [a, b] in self.non_manifold_edges.iter().take(10) {
+            write!(f, " {a}-{b}")?;
+        }
+        if self.non_manifold_edges.len() > 10 {
+            write!(f, " ...")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "boundary loops: {}", self.boundary_loops)?;
+        writeln!(f, "manifold: {}", self.is_manifold())?;
+        writeln!(f, "orientable: {}", self.is_orientable())?;
+        write!(f, "components: {}", self.components.len())?;
+        for (i, c) in self.components.iter().enumerate() {
+            let genus = c.genus.map_or("-".to_string(), |g| g.to_string());
+            write!(
+                f,
+                "\n  {i}: {} faces, {} vertices, {} boundary loops, euler {}, genus {genus}{}",
+                c.faces,
+                c.vertices,
+                c.boundary_loops,
+                c.euler,
+                if c.orientable { "" } else { ", not orientable" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_tetrahedra_and_strip() {
+        let tet = [[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]];
+        let mut mesh = Mesh {
+            vertices: vec![[0.0; 3]; 12],
+            faces: tet.to_vec(),
+            ..Default::default()
+        };
+        // Second tetrahedron touching the first at vertex 0
+        mesh.faces.extend(tet.map(|f| f.map(|v| [0, 4, 5, 6][v])));
+        // Strip, with a degenerate face and one out of range
+        mesh.faces
+            .extend([[7, 8, 9], [9, 8, 10], [7, 7, 8], [7, 8, 12]]);
+
+        let report = check(&mesh);
+        assert_eq!(report.degenerate, [10]);
+        assert_eq!(report.out_of_range, [11]);
+        assert_eq!(report.unreferenced, [11]);
+        assert!(report.non_manifold_edges.is_empty());
+        assert_eq!(report.non_manifold_vertices, [0]);
+        assert_eq!(report.boundary_loops, 1);
+        assert_eq!(report.components.len(), 2);
+        assert_eq!(report.components[0].genus, None);
+        assert_eq!(report.components[1].genus, Some(0));
+        assert_eq!(report.components[1].euler, 1);
+        assert!(report.to_json().contains("\"non_manifold_vertices\":[0]"));
+    }
+}
@@ -0,0 +1,236 @@
+// ,---------------------------------------------------------------------------
+// | Adaptive arithmetic coding
+// '---------------------------------------------------------------------------
+
+use bitvec::{order::Msb0, vec::BitVec, view::BitView};
+
+use crate::EdgeBreakerError;
+
+const BITS: u32 = 32;
+const TOP: u64 = (1 << BITS) - 1;
+const HALF: u64 = 1 << (BITS - 1);
+const QUARTER: u64 = 1 << (BITS - 2);
+
+const INCREMENT: u32 = 24;
+const MAX_TOTAL: u32 = 1 << 13;
+
+/// Frequency table over `N` symbols that adapts to the coded data.
+#[derive(Debug, Clone)]
+pub struct Model<const N: usize> {
+    freq: [u32; N],
+    total: u32,
+}
+
+impl<const N: usize> Default for Model<N> {
+    fn default() -> Self {
+        Self {
+            freq: [1; N],
+            total: N as u32,
+        }
+    }
+}
+
+impl<const N: usize> Model<N> {
+    fn range(&self, symbol: usize) -> (u32, u32) {
+        let low = self.freq[..symbol].iter().sum();
+        (low, low + self.freq[symbol])
+    }
+
+    fn update(&mut self, symbol: usize) {
+        self.freq[symbol] += INCREMENT;
+        self.total += INCREMENT;
+        if self.total > MAX_TOTAL {
+            self.total = 0;
+            for f in self.freq.iter_mut() {
+                *f = f.div_ceil(2);
+                self.total += *f;
+            }
+        }
+    }
+}
+
+pub struct Encoder {
+    low: u64,
+    high: u64,
+    pending: usize,
+    bits: BitVec<u8, Msb0>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            low: 0,
+            high: TOP,
+            pending: 0,
+            bits: BitVec::new(),
+        }
+    }
+
+    fn emit(&mut self, bit: bool) {
+        self.bits.push(bit);
+        for _ in 0..self.pending {
+            self.bits.push(!bit);
+        }
+        self.pending = 0;
+    }
+
+    pub fn encode<const N: usize>(&mut self, model: &mut Model<N>, symbol: usize) {
+        let (cum_low, cum_high) = model.range(symbol);
+        self.narrow(cum_low as u64, cum_high as u64, model.total as u64);
+        model.update(symbol);
+    }
+
+    /// Write the lowest `count` bits of `value` with equal probabilities.
+    pub fn encode_bits(&mut self, value: u64, count: u32) {
+        for i in (0..count).rev() {
+            let bit = (value >> i) & 1;
+            self.narrow(bit, bit + 1, 2);
+        }
+    }
+
+    fn narrow(&mut self, cum_low: u64, cum_high: u64, total: u64) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * cum_high / total - 1;
+        self.low += range * cum_low / total;
+
+        loop {
+            if self.high < HALF {
+                self.emit(false);
+            } else if self.low >= HALF {
+                self.emit(true);
+                self.low -= HALF;
+                self.high -= HALF;
+            } else if self.low >= QUARTER && self.high < HALF + QUARTER {
+                self.pending += 1;
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        self.pending += 1;
+        self.emit(self.low >= QUARTER);
+        self.bits.into_vec()
+    }
+}
+
+pub struct Decoder<'a> {
+    low: u64,
+    high: u64,
+    value: u64,
+    bits: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(bits: &'a [u8]) -> Self {
+        let mut decoder = Self {
+            low: 0,
+            high: TOP,
+            value: 0,
+            bits,
+            position: 0,
+        };
+        for _ in 0..BITS {
+            decoder.value = (decoder.value << 1) | decoder.next_bit();
+        }
+        decoder
+    }
+
+    fn next_bit(&mut self) -> u64 {
+        let bit = self
+            .bits
+            .view_bits::<Msb0>()
+            .get(self.position)
+            .is_some_and(|b| *b);
+        self.position += 1;
+        bit as u64
+    }
+
+    fn target(&self, total: u64) -> Result<u64, EdgeBreakerError> {
+        // Running far past the input means the stream was cut short
+        if self.position > self.bits.len() * 8 + BITS as usize {
+            return Err(EdgeBreakerError::CorruptHistory(
+                "arithmetic coded stream is truncated".to_string(),
+            ));
+        }
+
+        let range = self.high - self.low + 1;
+        Ok(((self.value - self.low + 1) * total - 1) / range)
+    }
+
+    pub fn decode<const N: usize>(
+        &mut self,
+        model: &mut Model<N>,
+    ) -> Result<usize, EdgeBreakerError> {
+        let target = self.target(model.total as u64)?;
+
+        let mut symbol = 0;
+        let mut cum_high = 0;
+        while symbol < N {
+            cum_high += model.freq[symbol] as u64;
+            if cum_high > target {
+                break;
+            }
+            symbol += 1;
+        }
+        if symbol == N {
+            return Err(EdgeBreakerError::CorruptHistory(
+                "arithmetic coded stream is corrupt".to_string(),
+            ));
+        }
+
+        let (cum_low, cum_high) = model.range(symbol);
+        self.narrow(cum_low as u64, cum_high as u64, model.total as u64);
+        model.update(symbol);
+
+        Ok(symbol)
+    }
+
+    /// Read `count` bits written by [`Encoder::encode_bits`].
+    pub fn decode_bits(&mut self, count: u32) -> Result<u64, EdgeBreakerError> {
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self.target(2)?;
+            self.narrow(bit, bit + 1, 2);
+            value = (value << 1) | bit;
+        }
+        Ok(value)
+    }
+
+    fn narrow(&mut self, cum_low: u64, cum_high: u64, total: u64) {
+        let range = self.high - self.low + 1;
+        self.high = self.low + range * cum_high / total - 1;
+        self.low += range * cum_low / total;
+
+        loop {
+            if self.high < HALF {
+                // Nothing to subtract
+            } else if self.low >= HALF {
+                self.low -= HALF;
+                self.high -= HALF;
+                self.value -= HALF;
+            } else if self.low >= QUARTER && self.high < HALF + QUARTER {
+                self.low -= QUARTER;
+                self.high -= QUARTER;
+                self.value -= QUARTER;
+            } else {
+                break;
+            }
+            self.low <<= 1;
+            self.high = (self.high << 1) | 1;
+            self.value = (self.value << 1) | self.next_bit();
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// ,---------------------------------------------------------------------------
+// | Attributes: per-corner texture coordinates and normals
+// '---------------------------------------------------------------------------
+
+use std::{collections::HashMap, ops::Range};
+
+use super::{
+    arithmetic::{Decoder, Encoder, Model},
+    chunks,
+    public::Attribute,
+};
+use crate::EdgeBreakerError;
+
+// Corners are visited in decoding order and their index is coded relative to
+// the attributes already used at the same vertex. Smooth regions are almost
+// free and seams only cost a short reference.
+const SAME: usize = 0; // most recently used attribute of the vertex
+const NEW: usize = 1; // next attribute in order of first use
+const LOCAL: usize = 2; // another attribute used at the vertex
+const GLOBAL: usize = 3; // any attribute used so far
+
+// Bits needed to store a value in 0..n
+fn width(n: usize) -> u32 {
+    usize::BITS - n.saturating_sub(1).leading_zeros()
+}
+
+/// Find the input face and rotation of every decoded face, such that
+/// `decoded[t][k] == faces[f][(k + r) % 3]` for the returned `(f, r)`.
+pub fn match_faces(
+    faces: &[[usize; 3]],
+    decoded: &[[usize; 3]],
+) -> Result<Vec<(usize, usize)>, EdgeBreakerError> {
+    let mut lookup: HashMap<[usize; 3], Vec<usize>> = HashMap::new();
+    for (i, f) in faces.iter().enumerate().rev() {
+        lookup.entry(*f).or_default().push(i);
+    }
+
+    decoded
+        .iter()
+        .map(|&[a, b, c]| {
+            [[a, b, c], [c, a, b], [b, c, a]]
+                .into_iter()
+                .enumerate()
+                .find_map(|(r, key)| Some((lookup.get_mut(&key)?.pop()?, r)))
+                .ok_or_else(|| {
+                    EdgeBreakerError::UnsupportedTopology(format!(
+                        "decoded face {:?} does not match any input face",
+                        [a, b, c]
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Encode the per-corner indices `corners` of the decoded `faces`.
+/// `vertex_count` bounds the vertex ids used in `faces`. Coding starts over
+/// at every face in `starts`. Returns the byte offset of each start and the
+/// number of values used before it.
+pub fn encode<const N: usize>(
+    values: &[[f32; N]],
+    faces: &[[usize; 3]],
+    corners: &[[usize; 3]],
+    vertex_count: usize,
+    starts: &[usize],
+) -> (Attribute<N>, Vec<(usize, usize)>) {
+    let mut seen: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    let mut remap = vec![None; values.len()];
+    let mut ordered = Vec::new();
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::with_capacity(starts.len());
+
+    for range in chunks(starts, faces.len()) {
+        let mut models: [Model<4>; 2] = Default::default();
+        let mut encoder = Encoder::new();
+        let first = ordered.len();
+        for (f, c) in faces[range.clone()].iter().zip(&corners[range.clone()]) {
+            for k in 0..3 {
+                let local = &mut seen[f[k]];
+                let model = &mut models[local.is_empty() as usize];
+
+                match remap[c[k]] {
+                    None => {
+                        encoder.encode(model, NEW);
+                        remap[c[k]] = Some(ordered.len());
+                        local.push(ordered.len());
+                        ordered.push(values[c[k]]);
+                    }
+                    Some(n) if local.last() == Some(&n) => encoder.encode(model, SAME),
+                    Some(n) => {
+                        if let Some(pos) = local.iter().position(|&i| i == n) {
+                            encoder.encode(model, LOCAL);
+                            encoder.encode_bits(pos as u64, width(local.len()));
+                            local.remove(pos);
+                        } else {
+                            encoder.encode(model, GLOBAL);
+                            encoder.encode_bits(n as u64, width(ordered.len()));
+                        }
+                        local.push(n);
+                    }
+                }
+            }
+        }
+        forget(&mut seen, &faces[range]);
+        offsets.push((bytes.len(), first));
+        bytes.extend(encoder.finish());
+    }
+
+    let attribute = Attribute {
+        values: ordered,
+        corners: bytes,
+    };
+    (attribute, offsets)
+}
+
+// Clear what was used at the vertices of `faces` before the next chunk
+fn forget(seen: &mut [Vec<usize>], faces: &[[usize; 3]]) {
+    for &v in faces.iter().flatten() {
+        seen[v].clear();
+    }
+}
+
+/// Decode the per-corner indices of `faces` into `attribute.values`. Each of
+/// `chunks` gives a run of faces, the byte offset their coding starts at and
+/// the number of values used before them.
+pub fn decode<const N: usize>(
+    attribute: &Attribute<N>,
+    faces: &[[usize; 3]],
+    vertex_count: usize,
+    chunks: &[(Range<usize>, (usize, usize))],
+) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let mut seen: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    let corrupt = |what: &str| {
+        EdgeBreakerError::CorruptHistory(format!("attribute stream references {what}"))
+    };
+
+    let mut corners = Vec::with_capacity(faces.len());
+    for (range, (offset, first)) in chunks {
+        let (Some(faces), Some(data), true) = (
+            faces.get(range.clone()),
+            attribute.corners.get(*offset..),
+            *first <= attribute.values.len(),
+        ) else {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "attribute corners of faces {range:?} at byte {offset} lie outside of the stream"
+            )));
+        };
+        let mut models: [Model<4>; 2] = Default::default();
+        let mut decoder = Decoder::new(data);
+        let mut used = *first;
+        for f in faces {
+            let mut c = [0; 3];
+            for k in 0..3 {
+                let local = &mut seen[f[k]];
+                let model = &mut models[local.is_empty() as usize];
+
+                c[k] = match decoder.decode(model)? {
+                    NEW => {
+                        if used >= attribute.values.len() {
+                            return Err(corrupt("more values than stored"));
+                        }
+                        used += 1;
+                        local.push(used - 1);
+                        used - 1
+                    }
+                    SAME => *local.last().ok_or_else(|| corrupt("an unused vertex"))?,
+                    LOCAL => {
+                        let pos = decoder.decode_bits(width(local.len()))? as usize;
+                        if pos >= local.len() {
+                            return Err(corrupt("a value outside of the vertex"));
+                        }
+                        let n = local.remove(pos);
+                        local.push(n);
+                        n
+                    }
+                    _ => {
+                        let n = decoder.decode_bits(width(used))? as usize;
+                        if n >= used {
+                            return Err(corrupt("a value that was not used yet"));
+                        }
+                        local.push(n);
+                        n
+                    }
+                };
+            }
+            corners.push(c);
+        }
+        forget(&mut seen, faces);
+    }
+
+    Ok(corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Mesh, compress, decompress};
+
+    #[test]
+    fn attributes_roundtrip() {
+        // Strip of four quads wrapped into a ring, with a texture seam where
+        // the ends meet and one normal per vertex
+        let mut mesh = Mesh::default();
+        for i in 0..4 {
+            mesh.vertices.push([i as f32, 0.0, 0.0]);
+            mesh.vertices.push([i as f32, 1.0, 0.0]);
+            mesh.normals.push([0.0, 0.0, i as f32]);
+            mesh.normals.push([0.0, 1.0, i as f32]);
+        }
+        for i in 0..5 {
+            mesh.texcoords.push([i as f32 / 4.0, 0.0]);
+            mesh.texcoords.push([i as f32 / 4.0, 1.0]);
+        }
+        for i in 0..4 {
+            let (a, b) = (2 * i, 2 * ((i + 1) % 4));
+            let t = 2 * i;
+            mesh.faces.push([a, b, b + 1]);
+            mesh.faces.push([a, b + 1, a + 1]);
+            mesh.face_texcoords.push([t, t + 2, t + 3]);
+            mesh.face_texcoords.push([t, t + 3, t + 1]);
+            mesh.face_normals.push([a, b, b + 1]);
+            mesh.face_normals.push([a, b + 1, a + 1]);
+        }
+
+        let decoded = decompress(&compress(&mesh).unwrap()).unwrap();
+        let corners = |m: &Mesh| {
+            let mut corners = (0..m.faces.len())
+                .flat_map(|f| {
+                    (0..3).map(move |k| {
+                        (
+                            m.vertices[m.faces[f][k]].map(f32::to_bits),
+                            m.texcoords[m.face_texcoords[f][k]].map(f32::to_bits),
+                            m.normals[m.face_normals[f][k]].map(f32::to_bits),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            corners.sort();
+            corners
+        };
+        assert_eq!(corners(&decoded), corners(&mesh));
+        assert_eq!(decoded.texcoords.len(), 10);
+    }
+}
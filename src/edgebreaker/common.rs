@@ -64,3 +64,17 @@ impl<T> IndexMut<Id> for Vec<T> {
         self.index_mut(index.0 - 1)
     }
 }
+
+impl<T> Index<Id> for [T] {
+    type Output = T;
+
+    fn index(&self, index: Id) -> &Self::Output {
+        self.index(index.0 - 1)
+    }
+}
+
+impl<T> IndexMut<Id> for [T] {
+    fn index_mut(&mut self, index: Id) -> &mut Self::Output {
+        self.index_mut(index.0 - 1)
+    }
+}
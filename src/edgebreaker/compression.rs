@@ -1,11 +1,10 @@
-use std::collections::HashMap;
-
-use crate::{edgebreaker::public::Op, obj::Obj};
+use crate::{EdgeBreakerError, edgebreaker::public::Op};
 use log::debug;
 
 use super::{
     EdgeBreaker,
     common::{Id, NULL},
+    manifold::Cut,
 };
 
 // .--------------------------------------------------------------------------.
@@ -16,7 +15,6 @@ use super::{
 pub struct HalfEdges {
     vertex_count: usize,
     triangle_count: usize,
-    conflicts: HashMap<(usize, usize), usize>,
     s: Vec<Id>,
     e: Vec<Id>,
     n: Vec<Id>,
@@ -25,88 +23,63 @@ pub struct HalfEdges {
 }
 
 impl HalfEdges {
-    pub fn init(obj: &Obj) -> Self {
-        let capacity = obj.faces.len() * 3;
-        let vertex_count = obj.vertices.len();
-        let mut conflicts: HashMap<(usize, usize), usize> = HashMap::new();
+    pub fn init(cut: &Cut) -> Self {
+        let capacity = cut.faces.len() * 3;
+        let vertex_count = cut.original.len();
         let mut s: Vec<Id> = vec![NULL; capacity];
         let mut e: Vec<Id> = vec![NULL; capacity];
         let mut n: Vec<Id> = vec![NULL; capacity];
         let mut p: Vec<Id> = vec![NULL; capacity];
         let mut o: Vec<Id> = vec![NULL; capacity];
 
-        let mut edge_map: HashMap<(usize, usize), Id> = HashMap::new();
-        for (t, face) in obj.faces.iter().enumerate() {
+        for (t, face) in cut.faces.iter().enumerate() {
             let offset = t * 3;
 
             // Construct half-edges from triangle
             for i in 0..3 {
                 let h = Id::from_offset(i + offset);
 
-                s[h] = Id::new(face[i]);
-                e[h] = Id::new(face[(i + 1) % 3]);
+                s[h] = Id::from_offset(face[i]);
+                e[h] = Id::from_offset(face[(i + 1) % 3]);
                 n[h] = Id::from_offset((i + 1) % 3 + offset);
                 p[h] = Id::from_offset((i + 2) % 3 + offset);
             }
 
-            // Check for collisions and fix boundary
+            // Glue to the opposite half-edges of earlier triangles
             for i in 0..3 {
                 let h = Id::from_offset(i + offset);
-                let a = s[h];
-                let b = e[h];
-
-                if let Some(&g) = edge_map.get(&(b.id(), a.id())) {
-                    // Fix next and previous for triangles
-                    let gN = n[g];
-                    let gP = p[g];
-                    let hN = n[h];
-                    let hP = p[h];
-
-                    // non-manifold edge.
-                    if gN == NULL || gP == NULL {
-                        // non-manifold edge.
-                        let edge = ((a.id()), b.id());
-                        let conflict_count = match conflicts.get(&edge) {
-                            Some(c) => *c,
-                            None => 0,
-                        };
-                        conflicts.insert(edge, conflict_count + 1);
-                    }
-                    // First collision: make half edges internal
-                    else {
-                        // Connect border loops
-                        n[hP] = gN;
-                        p[gN] = hP;
-                        n[gP] = hN;
-                        p[hN] = gP;
-
-                        // Remove border loop for colided half edges
-                        n[g] = NULL;
-                        p[g] = NULL;
-                        n[h] = NULL;
-                        p[h] = NULL;
-
-                        // h and g are opposites
-                        o[h] = g;
-                        o[g] = h;
-                    }
-                } else if let Some(_) = edge_map.get(&(a.id(), b.id())) {
-                    let edge = ((a.id()), b.id());
-                    let conflict_count = match conflicts.get(&edge) {
-                        Some(c) => *c,
-                        None => 0,
-                    };
-                    conflicts.insert(edge, conflict_count + 1);
-                } else {
-                    edge_map.insert((a.id(), b.id()), h);
-                }
+                let Some(g) = cut.opposite[h.offset()].filter(|&g| g < h.offset()) else {
+                    continue;
+                };
+                let g = Id::from_offset(g);
+
+                // Fix next and previous for triangles
+                let gN = n[g];
+                let gP = p[g];
+                let hN = n[h];
+                let hP = p[h];
+
+                // Connect border loops
+                n[hP] = gN;
+                p[gN] = hP;
+                n[gP] = hN;
+                p[hN] = gP;
+
+                // Remove border loop for colided half edges
+                n[g] = NULL;
+                p[g] = NULL;
+                n[h] = NULL;
+                p[h] = NULL;
+
+                // h and g are opposites
+                o[h] = g;
+                o[g] = h;
             }
         }
 
         Self {
-            vertex_count: vertex_count,
-            triangle_count: obj.faces.len(),
-            conflicts,
+            vertex_count,
+            triangle_count: cut.faces.len(),
             s,
             e,
             n,
@@ -159,53 +132,14 @@ enum Mark {
 fn markEdges(
     mark: Mark,
     gate: Id,
-    he: &mut HalfEdges,
+    he: &HalfEdges,
     previous: &mut Vec<Id>,
-    vm: &mut Vec<Mark>,
-    hm: &mut Vec<Mark>,
-    duplicated: &mut Vec<Id>,
+    vm: &mut [Mark],
+    hm: &mut [Mark],
 ) {
     let mut g = gate;
     loop {
-        let mut sv = he.s[g];
-        let mut ev = he.e[g];
-
-        // Fix conflicts
-        let edge = (sv.id(), ev.id());
-        if let Some(&c) = he.conflicts.get(&edge) {
-            if c != 0 {
-                // Assign new IDs
-                vm.push(vm[sv]);
-                vm.push(vm[ev]);
-
-                duplicated.push(sv);
-                sv = Id::new(he.vertex_count + duplicated.len());
-                duplicated.push(ev);
-                ev = Id::new(he.vertex_count + duplicated.len());
-
-                // Walk around vertices and assign new ones
-                he.s[g] = sv;
-                he.e[g] = ev;
-
-                let mut b = he.p[g];
-                while b != NULL {
-                    he.e[b] = sv;
-                    b = HalfEdges::n(b);
-                    he.s[b] = sv;
-                    b = he.o[b];
-                }
-
-                b = g;
-                while b != NULL {
-                    he.e[b] = ev;
-                    b = HalfEdges::n(b);
-                    he.s[b] = ev;
-                    b = he.o[b];
-                }
-
-                he.conflicts.insert(edge, c - 1);
-            }
-        }
+        let ev = he.e[g];
 
         // Mark as boundary
         if mark == Mark::External1 {
@@ -220,95 +154,158 @@ fn markEdges(
     }
 }
 
-pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
+// Start a component without boundary on the edge of `gate`
+fn markClosed(
+    gate: Id,
+    he: &mut HalfEdges,
+    previous: &mut Vec<Id>,
+    vm: &mut [Mark],
+    hm: &mut [Mark],
+) {
+    markEdges(Mark::External1, gate, he, previous, vm, hm);
+
+    // Triangulation has no edges. Make one
+    he.n[gate] = he.o[gate];
+    he.p[gate] = he.o[gate];
+    he.n[he.o[gate]] = gate;
+    he.p[he.o[gate]] = gate;
+    hm[he.o[gate]] = Mark::External1;
+    vm[he.s[gate]] = Mark::External1;
+    previous.push(he.s[gate]);
+}
+
+/// Vertex in the order of the first triangle that uses it, with the
+/// vertices of its parallelogram prediction `a + b - c`
+pub type Visit = (Id, Option<[Id; 3]>);
+
+// Visit the triangle of gate `g`. Its vertices that no earlier triangle uses
+// are predicted by the parallelogram over the edge opposite them, when the
+// triangle across that edge was visited before.
+fn visit(he: &HalfEdges, g: Id, visited: &mut [bool], seen: &mut [bool], visits: &mut Vec<Visit>) {
+    for h in [g, HalfEdges::n(g), HalfEdges::p(g)] {
+        let v = he.v(h);
+        if !seen[v.offset()] {
+            seen[v.offset()] = true;
+            let across = he.o[h];
+            let prediction = (across != NULL && visited[across.offset() / 3])
+                .then(|| [he.s[h], he.e[h], he.v(across)]);
+            visits.push((v, prediction));
+        }
+    }
+    visited[g.offset() / 3] = true;
+}
+
+// Opposite of `h`, in the triangle the new boundary runs through. Edges on
+// the mesh boundary or into a visited triangle leave a boundary the history
+// cannot describe.
+fn across(he: &HalfEdges, visited: &[bool], h: Id) -> Result<Id, EdgeBreakerError> {
+    let o = he.o[h];
+    if o == NULL || visited[o.offset() / 3] {
+        return Err(EdgeBreakerError::UnsupportedTopology(format!(
+            "no unvisited triangle across {}",
+            he.print_edge(h)
+        )));
+    }
+    Ok(o)
+}
+
+// Call `f` with the edges of the boundary loop of `start`, and return their
+// number. Links that do not lead back to `start` within the number of
+// half-edges leave the traversal on a boundary it cannot finish.
+fn walk<F: FnMut(Id)>(he: &HalfEdges, start: Id, mut f: F) -> Result<usize, EdgeBreakerError> {
+    let mut b = start;
+    for len in 1..=he.n.len() {
+        f(b);
+        b = he.n[b];
+        if b == start {
+            return Ok(len);
+        }
+        if b == NULL {
+            break;
+        }
+    }
+    Err(EdgeBreakerError::UnsupportedTopology(format!(
+        "boundary loop of {} does not close",
+        he.print_edge(start)
+    )))
+}
+
+// Rotate around the tip of the triangle of `g` until reaching a marked edge.
+// It ends at the tip on the loop the triangle touches, which the mark of the
+// tip cannot tell when the tip is on two loops after an S.
+fn rotate(he: &HalfEdges, hm: &[Mark], g: Id) -> Result<Id, EdgeBreakerError> {
+    let mut b = HalfEdges::n(g);
+    for _ in 0..he.o.len() {
+        if hm[b] != Mark::Unmarked {
+            return Ok(b);
+        }
+        if he.o[b] == NULL {
+            break;
+        }
+        b = HalfEdges::p(he.o[b]);
+    }
+    Err(EdgeBreakerError::UnsupportedTopology(format!(
+        "no boundary around the tip of {}",
+        he.print_edge(g)
+    )))
+}
+
+pub fn compress(he: &mut HalfEdges) -> Result<(EdgeBreaker, Vec<Visit>), EdgeBreakerError> {
     let mut history = Vec::new();
     let mut previous = Vec::new();
     let mut lengths = Vec::new();
     let mut m_table = Vec::new();
     let mut stack = Vec::new();
-    let mut duplicated = Vec::new();
     let mut components = Vec::new();
+    let mut closed = 0;
 
     let mut vm = vec![Mark::Unmarked; he.vertex_count];
     let mut hm = vec![Mark::Unmarked; he.triangle_count * 3];
+    let mut visited = vec![false; he.triangle_count];
+    let mut seen = vec![false; he.vertex_count];
+    let mut visits = Vec::new();
+
+    // Start at the first boundary, the others are holes. Meshes without
+    // boundary are started like any other closed component below.
+    if let Some(i) = he.n.iter().position(|&x| x != NULL) {
+        let gate = Id::from_offset(i);
+        debug!("gate: {}", he.print_edge(gate));
+        markEdges(Mark::External1, gate, he, &mut previous, &mut vm, &mut hm);
+        stack.push(gate);
+    }
 
-    debug!("conflicts: {:?}", he.conflicts);
-
-    // Find the first gate
-    let gate = match he.n.iter().position(|&x| x != NULL) {
-        Some(i) => Id::from_offset(i),
-        None => Id::new(1),
-    };
-
-    debug!("gate: {}", he.print_edge(gate));
-
-    // Mark first boundary
-    markEdges(
-        Mark::External1,
-        gate,
-        he,
-        &mut previous,
-        &mut vm,
-        &mut hm,
-        &mut duplicated,
-    );
-
-    if he.n[gate] == NULL {
-        // Triangulation has no edges. Make one
-        he.n[gate] = he.o[gate];
-        he.p[gate] = he.o[gate];
-        he.n[he.o[gate]] = gate;
-        he.p[he.o[gate]] = gate;
-        hm[he.o[gate]] = Mark::External1;
-        vm[he.s[gate]] = Mark::External1;
-        previous.push(he.s[gate]);
-    } else {
-        // Find other external edges (Holes)
-        while let Some(i) =
-            he.n.iter()
-                .zip(&hm)
-                .position(|(&n, &m)| n != NULL && m == Mark::Unmarked)
-        {
-            let _gate = Id::from_offset(i);
-            markEdges(
-                Mark::External2,
-                _gate,
-                he,
-                &mut previous,
-                &mut vm,
-                &mut hm,
-                &mut duplicated,
-            );
-            components.push(_gate);
-        }
+    // Find other external edges (Holes)
+    while let Some(i) =
+        he.n.iter()
+            .zip(&hm)
+            .position(|(&n, &m)| n != NULL && m == Mark::Unmarked)
+    {
+        let _gate = Id::from_offset(i);
+        markEdges(Mark::External2, _gate, he, &mut previous, &mut vm, &mut hm);
+        components.push(_gate);
     }
 
     // Main algorithm loop
-    stack.push(gate);
     'main: loop {
         while let Some(g) = stack.pop() {
+            let ops = history.len();
             if let Mark::External3(_g) = hm[g] {
                 // Mark with External1
-                let mut b = g;
-                loop {
+                walk(he, g, |b| {
                     hm[b] = Mark::External1;
                     vm[he.e[b]] = Mark::External1;
-                    b = he.n[b];
-                    if he.e[b] == he.e[g] {
-                        break;
-                    }
-                }
+                })?;
             }
 
             match vm[he.v(g)] {
                 Mark::Unmarked => {
                     debug!("Case C");
                     // Case C
+                    let gpo = across(he, &visited, HalfEdges::p(g))?;
+                    let gno = across(he, &visited, HalfEdges::n(g))?;
                     history.push(Op::C);
                     previous.push(he.v(g));
 
-                    let gpo = he.o[HalfEdges::p(g)];
-                    let gno = he.o[HalfEdges::n(g)];
                     let gv = he.v(g);
                     let gN = he.n[g];
                     let gP = he.p[g];
@@ -336,36 +333,32 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
 
                 Mark::External2 => {
                     // Case M
-                    history.push(Op::H);
                     debug!("Case M");
 
-                    let gpo = he.o[HalfEdges::p(g)];
-                    let gno = he.o[HalfEdges::n(g)];
+                    let gpo = across(he, &visited, HalfEdges::p(g))?;
+                    let gno = across(he, &visited, HalfEdges::n(g))?;
                     let gN = he.n[g];
                     let gP = he.p[g];
 
+                    let b = rotate(he, &hm, g)?;
+                    if hm[b] != Mark::External2 {
+                        return Err(EdgeBreakerError::UnsupportedTopology(format!(
+                            "hole vertex of {} is on another boundary",
+                            he.print_edge(g)
+                        )));
+                    }
+                    history.push(Op::H);
+
                     hm[g] = Mark::Unmarked;
                     hm[gpo] = Mark::External1;
                     hm[gno] = Mark::External1;
 
-                    let mut b = HalfEdges::n(g);
-                    while hm[b] != Mark::External2 {
-                        b = HalfEdges::p(he.o[b]);
-                    }
-
                     // Hole traversal
-                    let mut len = 0;
-                    loop {
-                        let bs = he.s[b];
+                    let len = walk(he, b, |b| {
                         hm[b] = Mark::External1;
-                        vm[bs] = Mark::External1;
-                        len += 1;
+                        vm[he.s[b]] = Mark::External1;
                         previous.push(he.e[b]);
-                        b = he.n[b];
-                        if he.e[b] == he.s[gno] {
-                            break;
-                        }
-                    }
+                    })?;
                     lengths.push(len);
 
                     // Link 1
@@ -403,11 +396,11 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
                         } else {
                             // Case L
                             debug!("Case L");
+                            let gno = across(he, &visited, HalfEdges::n(g))?;
                             history.push(Op::L);
 
                             let gP = he.p[g];
                             let gPP = he.p[gP];
-                            let gno = he.o[HalfEdges::n(g)];
                             let gN = he.n[g];
 
                             // Flags
@@ -429,11 +422,11 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
                         if HalfEdges::n(g) == he.n[g] {
                             // Case R
                             debug!("Case R");
+                            let gpo = across(he, &visited, HalfEdges::p(g))?;
                             history.push(Op::R);
 
                             let gN = he.n[g];
                             let gNN = he.n[gN];
-                            let gpo = he.o[HalfEdges::p(g)];
                             let gP = he.p[g];
 
                             // Flags
@@ -451,64 +444,46 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
 
                             stack.push(gpo);
                         } else {
-                            match vm[he.v(g)] {
+                            // The tip is on the current loop for S, on one
+                            // split off earlier for M'
+                            let b = rotate(he, &hm, g)?;
+                            match hm[b] {
                                 Mark::External3(split_g) => {
                                     // Case M'
                                     debug!("Case M'");
                                     debug!("split_g: {:?}", split_g);
 
-                                    // Mark with External1
-                                    let mut b = split_g;
-                                    let mut l = 0;
-                                    loop {
-                                        if hm[b] != Mark::External3(split_g) {
-                                            debug!("mark: {:?}", hm[b]);
-                                            debug!("b: {:?}", (he.s[b], he.e[b]));
-                                        }
+                                    let gpo = across(he, &visited, HalfEdges::p(g))?;
+                                    let gno = across(he, &visited, HalfEdges::n(g))?;
 
-                                        hm[b] = Mark::External1;
-                                        vm[he.e[b]] = Mark::External1;
-                                        b = he.n[b];
-                                        l += 1;
-                                        if he.e[b] == he.e[split_g] {
-                                            break;
-                                        }
-                                    }
-
-                                    // Check if this is a self merge
-                                    if he.e[split_g] == he.e[g] {
-                                        stack.push(g);
-                                        continue;
-                                    }
-
-                                    // Calculate offset
-                                    b = split_g;
-                                    let mut o = 0;
-                                    loop {
-                                        if he.e[b] == he.v(g) {
-                                            break;
+                                    // Length of the merged loop and offset of b on it
+                                    let mut k = 0;
+                                    let mut o = None;
+                                    let l = walk(he, split_g, |d| {
+                                        if d == b {
+                                            o = Some(k);
                                         }
-                                        o += 1;
-                                        b = he.n[b];
-                                        if he.e[b] == he.e[split_g] {
-                                            break;
-                                        }
-                                    }
+                                        k += 1;
+                                    })?;
+                                    let Some(o) = o else {
+                                        return Err(EdgeBreakerError::UnsupportedTopology(
+                                            format!(
+                                                "merge edge {} is not on its loop",
+                                                he.print_edge(b)
+                                            ),
+                                        ));
+                                    };
 
                                     // Find split_g in stack
                                     let Some(p) = stack.iter().position(|&_g| split_g == _g) else {
-                                        panic!(
-                                            "Invalid stack structure. Did not find split_g in stack!"
-                                        );
+                                        return Err(EdgeBreakerError::UnsupportedTopology(
+                                            "merge target is not on the split stack".to_string(),
+                                        ));
                                     };
 
                                     history.push(Op::M);
                                     m_table.push((p, o, l));
 
-                                    let gp = HalfEdges::p(g);
-                                    let gn = HalfEdges::n(g);
-                                    let gpo = he.o[gp];
-                                    let gno = he.o[gn];
                                     let gP = he.p[g];
                                     let gN = he.n[g];
 
@@ -533,14 +508,21 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
                                     // Link 4
                                     he.n[gno] = gN;
                                     he.p[gN] = gno;
+
+                                    // The merged loop waits on the stack as
+                                    // split_g, like the decoder continues
+                                    walk(he, split_g, |d| {
+                                        hm[d] = Mark::External3(split_g);
+                                        vm[he.e[d]] = Mark::External3(split_g);
+                                    })?;
                                 }
                                 Mark::External1 => {
                                     // Case S
                                     debug!("Case S");
-                                    history.push(Op::S);
 
-                                    let gno = he.o[HalfEdges::n(g)];
-                                    let gpo = he.o[HalfEdges::p(g)];
+                                    let gno = across(he, &visited, HalfEdges::n(g))?;
+                                    let gpo = across(he, &visited, HalfEdges::p(g))?;
+                                    history.push(Op::S);
                                     let gN = he.n[g];
                                     let gP = he.p[g];
 
@@ -549,19 +531,10 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
                                     hm[gpo] = Mark::External1;
                                     hm[gno] = Mark::External1;
 
-                                    // Find b by rotating around v
-                                    let mut b = HalfEdges::n(g);
-                                    while hm[b] == Mark::Unmarked {
-                                        b = HalfEdges::p(he.o[b]);
-                                    }
-
                                     debug!("g: {}", he.print_edge(g));
                                     debug!("gpo: {}", he.print_edge(gpo));
                                     debug!("gno: {}", he.print_edge(gno));
-                                    debug!("gP: {}", he.print_edge(gP));
-                                    debug!("gN: {}", he.print_edge(gN));
                                     debug!("b: {}", he.print_edge(b));
-                                    debug!("hm[b]: {:?}", hm[b]);
 
                                     // Link 1
                                     he.n[gP] = gpo;
@@ -580,91 +553,60 @@ pub fn compress(he: &mut HalfEdges) -> EdgeBreaker {
                                     he.n[gno] = gN;
                                     he.p[gN] = gno;
 
-                                    // // Mark left loop with External3
-                                    let mut b = gpo;
-                                    debug!("marking 3");
-                                    let should_mark = loop {
-                                        match hm[b] {
-                                            Mark::External3(_) => {
-                                                break false;
-                                            }
-                                            _ => {}
-                                        }
-
-                                        match vm[he.e[b]] {
-                                            Mark::External3(_) => {
-                                                break false;
-                                            }
-                                            _ => {}
-                                        }
-
-                                        b = he.n[b];
-                                        if he.e[b] == he.e[gpo] {
-                                            break true;
-                                        }
-                                    };
-
-                                    if should_mark {
-                                        loop {
-                                            // debug!("b: {:?}", (he.s[b], he.e[b]));
-                                            debug!("overriding: {:?}", hm[b]);
-                                            debug!("vertex: {:?}", vm[he.e[b]]);
-                                            hm[b] = Mark::External3(gpo);
-                                            vm[he.e[b]] = Mark::External3(gpo);
-                                            b = he.n[b];
-                                            if he.e[b] == he.e[gpo] {
-                                                break;
-                                            }
-                                        }
-                                    }
-
-                                    debug!("g: {:?}", (he.s[g], he.e[g]));
-                                    debug!("gpo: {:?}", (he.s[gpo], he.e[gpo]));
-                                    debug!("hm[gpo]: {:?}", hm[gpo]);
+                                    // Mark left loop with External3
+                                    walk(he, gpo, |b| {
+                                        hm[b] = Mark::External3(gpo);
+                                        vm[he.e[b]] = Mark::External3(gpo);
+                                    })?;
 
-                                    // s_stack.push(history.len() - 1);
                                     stack.push(gpo);
                                     stack.push(gno);
                                 }
-                                _ => {
-                                    panic!("cannot happen");
+                                Mark::Unmarked | Mark::External2 => {
+                                    return Err(EdgeBreakerError::UnsupportedTopology(format!(
+                                        "unexpected boundary mark at {}",
+                                        he.print_edge(b)
+                                    )));
                                 }
                             }
                         }
                     }
                 }
             }
+            if history.len() > ops {
+                visit(he, g, &mut visited, &mut seen, &mut visits);
+            }
             debug!("hist.len {}", history.len());
         }
 
         while let Some(_gate) = components.pop() {
             if hm[_gate] == Mark::External2 {
-                markEdges(
-                    Mark::External1,
-                    _gate,
-                    he,
-                    &mut previous,
-                    &mut vm,
-                    &mut hm,
-                    &mut duplicated,
-                );
+                markEdges(Mark::External1, _gate, he, &mut previous, &mut vm, &mut hm);
                 stack.push(_gate);
                 continue 'main;
             }
         }
-        break;
-    }
 
-    for v in previous.iter_mut() {
-        if v.id() > he.vertex_count {
-            *v = duplicated[v.id() - he.vertex_count - 1];
+        // Closed components have no boundary, start them at an untouched vertex
+        if let Some(i) = (closed..he.triangle_count * 3)
+            .find(|&i| vm[he.s[Id::from_offset(i)]] == Mark::Unmarked)
+        {
+            closed = i;
+            let _gate = Id::from_offset(i);
+            markClosed(_gate, he, &mut previous, &mut vm, &mut hm);
+            stack.push(_gate);
+            continue 'main;
         }
+        break;
     }
 
-    EdgeBreaker {
-        history,
-        previous,
-        lengths,
-        m_table,
-    }
+    Ok((
+        EdgeBreaker {
+            history,
+            previous,
+            lengths,
+            m_table,
+        },
+        visits,
+    ))
 }
@@ -1,17 +1,92 @@
 use log::debug;
 
-use crate::edgebreaker::common::{Id, NULL};
+use crate::{
+    EdgeBreakerError,
+    edgebreaker::common::{Id, NULL},
+};
 
 use super::{EdgeBreaker, public::Op};
 
-pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
-    let t = eb.history.len();
+/// Offset of every S of a component, in history order. The history is
+/// counted backwards, where the length of every boundary loop is known: S
+/// joins the loop it split off, whose length gives its offset, and M splits
+/// the loop it merged back off the one it continues.
+pub fn split_offsets(
+    ops: &[Op],
+    lengths: &[usize],
+    merges: &[(usize, usize, usize)],
+) -> Result<Vec<usize>, EdgeBreakerError> {
+    let corrupt = |msg: &str| EdgeBreakerError::CorruptHistory(msg.to_string());
+    let mut offsets = vec![0; ops.iter().filter(|&&op| op == Op::S).count()];
+    let (mut s, mut li, mut mi) = (offsets.len(), lengths.len(), merges.len());
+    let mut len: Option<usize> = None;
+    let mut stack = Vec::new();
+
+    for op in ops.iter().rev() {
+        if *op == Op::E {
+            stack.extend(len);
+            len = Some(3);
+            continue;
+        }
+        let n = len.ok_or_else(|| corrupt("operation after the end of a component"))?;
+        len = match op {
+            Op::C => n.checked_sub(1),
+            Op::R | Op::L => Some(n + 1),
+            Op::S => {
+                s -= 1;
+                offsets[s] = n
+                    .checked_sub(2)
+                    .ok_or_else(|| corrupt("S on a loop of fewer than 2 edges"))?;
+                let a = stack
+                    .pop()
+                    .ok_or_else(|| corrupt("S without a matching E"))?;
+                (n + a).checked_sub(1)
+            }
+            Op::H => {
+                li -= 1;
+                n.checked_sub(lengths[li] + 1)
+            }
+            Op::M => {
+                mi -= 1;
+                let (p, _, l) = merges[mi];
+                stack.push(n);
+                let merged = stack
+                    .get_mut(p)
+                    .ok_or_else(|| corrupt("M references a missing split"))?;
+                let n = merged.checked_sub(l + 1);
+                *merged = l;
+                n
+            }
+            Op::E => unreachable!(),
+        };
+        if len.is_none() {
+            return Err(corrupt("boundary loop of negative length"));
+        }
+    }
+    Ok(offsets)
+}
+
+pub fn decompress(eb: &EdgeBreaker) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let mut faces = Vec::with_capacity(eb.history.len());
+    decompress_each(eb, |f| {
+        faces.push(f);
+        Ok(())
+    })?;
+    Ok(faces)
+}
+
+/// Decode the history of `eb` and pass every triangle to `emit` as soon as it
+/// is generated, already mapped to its final vertex ids. Stops at the first
+/// error, including one returned by `emit`.
+pub fn decompress_each<F>(eb: &EdgeBreaker, mut emit: F) -> Result<(), EdgeBreakerError>
+where
+    F: FnMut([usize; 3]) -> Result<(), EdgeBreakerError>,
+{
     let mut components = Vec::new();
     let mut d: i32 = 0; // |S| - |E|
     let mut c: usize = 0; // |C| = |V_i|
     let mut e: i32 = 0; // 3|E| + |L| + |R| - |C| - |S| = |V_e|
     let mut s: usize = 0; // |S|
-    let mut stack: Vec<(i32, usize)> = Vec::new();
     let mut offsets: Vec<usize> = vec![0; eb.history.iter().filter(|&o| *o == Op::S).count()];
     let mut edge_count = 0;
     let mut vertex_count = 0;
@@ -19,6 +94,8 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
     let mut a = 0;
     let mut li = 0;
     let mut mi = 0;
+    // First operation, S, hole and merge of the current component
+    let (mut first, mut s0, mut l0, mut m0) = (0, 0, 0, 0);
 
     // Create bounding loop
     let mut end = vec![NULL; edge_count];
@@ -28,11 +105,10 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
     // .----------------------------------------
     // | Preprocessing phase
 
-    for op in eb.history.iter() {
+    for (j, op) in eb.history.iter().enumerate() {
         match op {
             Op::S => {
                 e -= 1;
-                stack.push((e, s));
                 s += 1;
                 d += 1;
                 a += 1;
@@ -41,6 +117,12 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::E => {
                 e += 3;
                 if d <= 0 {
+                    if e < 1 {
+                        return Err(EdgeBreakerError::CorruptHistory(format!(
+                            "component closes with a boundary of {e} edges"
+                        )));
+                    }
+
                     let new_edge_count = edge_count + a + e as usize;
                     end.resize(new_edge_count, NULL);
                     next.resize(new_edge_count, NULL);
@@ -55,6 +137,10 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                     }
 
                     components.push((Id::new(1 + edge_count), bc));
+                    let ops = &eb.history[first..=j];
+                    let split = split_offsets(ops, &eb.lengths[l0..li], &eb.m_table[m0..mi])?;
+                    offsets[s0..s].copy_from_slice(&split);
+                    (first, s0, l0, m0) = (j + 1, s, li, mi);
                     edge_count = new_edge_count;
                     vertex_count += bc + h + c;
                     debug!("components: {:?}", components.last());
@@ -63,11 +149,9 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                     e = 0;
                     c = 0;
                     d = 0;
+                    h = 0;
+                    a = 0;
                 } else {
-                    let (_e, _s) = stack.pop().expect("(e,s) stack prematurely empty!");
-                    offsets[_s] = (e - _e - 2)
-                        .try_into()
-                        .expect("Encountered negative S offset!");
                     d -= 1;
                 }
             }
@@ -89,38 +173,64 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::H => {
                 let l = eb.lengths[li];
                 e -= l as i32 + 1;
-                h += l + 1;
+                h += l;
                 li += 1;
                 a += l + 1;
             }
             Op::M => {
-                let (p, _, l) = eb.m_table[mi];
+                let (p, _, _) = eb.m_table[mi];
                 mi += 1;
 
                 e -= 1;
                 a += 1;
-                let (_e, _s) = stack.remove(p);
-
-                offsets[_s] = (-_e - l as i32)
-                    .try_into()
-                    .expect("Encountered negative S offset!");
+                if p as i32 >= d {
+                    return Err(EdgeBreakerError::CorruptHistory(format!(
+                        "M references stack position {p} of {d}"
+                    )));
+                }
                 d -= 1;
             }
         }
     }
 
+    if d != 0 || e != 0 || c != 0 {
+        return Err(EdgeBreakerError::CorruptHistory(
+            "history ends in the middle of a component".to_string(),
+        ));
+    }
+
     // '----------------------------------------
 
     // .----------------------------------------
     // | Generation phase
 
-    let mut tv: Vec<[usize; 3]> = Vec::with_capacity(t);
+    let mut emit_face = |t: [usize; 3]| {
+        let mut f = [0; 3];
+        for k in 0..3 {
+            if t[k] > eb.previous.len() {
+                return Err(EdgeBreakerError::TableMismatch(format!(
+                    "history references vertex {}, but only {} are known",
+                    t[k],
+                    eb.previous.len()
+                )));
+            }
+            f[k] = eb.previous[t[k] - 1].offset();
+        }
+        emit(f)
+    };
     let mut ci = 0;
-    let (mut g, _e) = components[ci];
+    let Some(&(mut g, _e)) = components.first() else {
+        if eb.history.is_empty() {
+            return Ok(());
+        }
+        return Err(EdgeBreakerError::CorruptHistory(
+            "history does not contain a complete component".to_string(),
+        ));
+    };
     ci += 1;
 
-    let mut vc = _e as usize;
-    let mut ec: usize = _e as usize;
+    let mut vc = _e;
+    let mut ec: usize = _e;
     s = 0;
     li = 0;
     mi = 0;
@@ -132,7 +242,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::C => {
                 let gp = prev[g];
                 vc += 1;
-                tv.push([end[gp].id(), end[g].id(), vc]);
+                emit_face([end[gp].id(), end[g].id(), vc])?;
 
                 ec += 1;
                 let a = Id::new(ec);
@@ -147,7 +257,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::R => {
                 let gp = prev[g];
                 let gn = next[g];
-                tv.push([end[gp].id(), end[g].id(), end[gn].id()]);
+                emit_face([end[gp].id(), end[g].id(), end[gn].id()])?;
                 next[gp] = gn;
                 prev[gn] = gp;
                 g = gn;
@@ -156,7 +266,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::L => {
                 let gp = prev[g];
                 let gpp = prev[gp];
-                tv.push([end[gp].id(), end[g].id(), end[gpp].id()]);
+                emit_face([end[gp].id(), end[g].id(), end[gpp].id()])?;
 
                 prev[g] = gpp;
                 next[gpp] = g;
@@ -165,7 +275,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
             Op::E => {
                 let gp = prev[g];
                 let gn = next[g];
-                tv.push([end[gp].id(), end[g].id(), end[gn].id()]);
+                emit_face([end[gp].id(), end[g].id(), end[gn].id()])?;
 
                 if let Some(_g) = stack.pop() {
                     g = _g;
@@ -173,7 +283,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                     debug!("new component!");
                     let (_g, _e) = components[ci];
                     g = _g;
-                    ec += _e as usize;
+                    ec += _e;
                     vc += _e;
                     ci += 1;
                     debug!("g: {:?}", g);
@@ -190,7 +300,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                 }
                 s += 1;
 
-                tv.push([end[gp].id(), end[g].id(), end[d].id()]);
+                emit_face([end[gp].id(), end[g].id(), end[d].id()])?;
 
                 ec += 1;
                 let a = Id::new(ec);
@@ -208,7 +318,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
 
             Op::H => {
                 let gp = prev[g];
-                tv.push([end[gp].id(), end[g].id(), vc + 1]);
+                emit_face([end[gp].id(), end[g].id(), vc + 1])?;
 
                 let mut d = gp;
                 let l = eb.lengths[li];
@@ -243,7 +353,7 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                 }
                 let dn = next[d];
 
-                tv.push([end[gp].id(), end[g].id(), end[d].id()]);
+                emit_face([end[gp].id(), end[g].id(), end[d].id()])?;
 
                 ec += 1;
                 let a = Id::new(ec);
@@ -255,13 +365,18 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
                 next[d] = g;
                 prev[g] = d;
 
-                g = stack.pop().expect("Invalid decompression stack");
+                let Some(_g) = stack.pop() else {
+                    return Err(EdgeBreakerError::CorruptHistory(
+                        "M without a pending split".to_string(),
+                    ));
+                };
+                g = _g;
             }
         }
         // debug!("after: {:?}", op);
         // debug!(
         //     "last: {:?}",
-        //     tv.last().expect("test").map(|v| eb.previous[v - 1].id())
+        //     faces.last().expect("test").map(|v| eb.previous[v - 1].id())
         // );
         // debug!("next: {:?}", next);
         // debug!("end: {:?}", end);
@@ -269,10 +384,12 @@ pub fn decompress(eb: &EdgeBreaker) -> Vec<[usize; 3]> {
 
     // '----------------------------------------
 
-    for t in tv.iter_mut() {
-        for v in t.iter_mut() {
-            *v = eb.previous[*v - 1].id();
-        }
+    if vc > eb.previous.len() {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "history references {vc} vertices, but only {} are known",
+            eb.previous.len()
+        )));
     }
-    tv
+
+    Ok(())
 }
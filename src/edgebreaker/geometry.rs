@@ -0,0 +1,315 @@
+// ,---------------------------------------------------------------------------
+// | Geometry: quantization and parallelogram prediction
+// '---------------------------------------------------------------------------
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use super::{
+    arithmetic::{Decoder, Encoder, Model},
+    chunks,
+    public::QuantizedGeometry,
+};
+use crate::EdgeBreakerError;
+
+// Residuals are coded as their bit length followed by the remaining bits and
+// a sign. Bit lengths are modelled separately per axis and per predictor.
+const CLASSES: usize = 35;
+
+#[derive(Clone, Copy)]
+enum Predictor {
+    Parallelogram = 0,
+    Delta = 1,
+}
+
+struct Models {
+    classes: [[Model<CLASSES>; 3]; 2],
+}
+
+impl Models {
+    fn new() -> Self {
+        Self {
+            classes: Default::default(),
+        }
+    }
+}
+
+struct Quantizer {
+    min: [f32; 3],
+    scale: [f32; 3],
+    max_value: i64,
+}
+
+impl Quantizer {
+    fn new(bits: u8, min: [f32; 3], max: [f32; 3]) -> Self {
+        let max_value = (1i64 << bits) - 1;
+        let scale = std::array::from_fn(|i| {
+            let extent = max[i] - min[i];
+            if extent > 0.0 {
+                extent / max_value as f32
+            } else {
+                0.0
+            }
+        });
+        Self {
+            min,
+            scale,
+            max_value,
+        }
+    }
+
+    fn quantize(&self, v: [f32; 3]) -> [i64; 3] {
+        std::array::from_fn(|i| {
+            if self.scale[i] == 0.0 {
+                0
+            } else {
+                (((v[i] - self.min[i]) / self.scale[i]).round() as i64).clamp(0, self.max_value)
+            }
+        })
+    }
+
+    fn dequantize(&self, q: [i64; 3]) -> [f32; 3] {
+        std::array::from_fn(|i| self.min[i] + q[i] as f32 * self.scale[i])
+    }
+}
+
+// Prediction for a vertex given the already known quantized positions
+fn predict(
+    q: &[Option<[i64; 3]>],
+    prediction: Option<[usize; 3]>,
+    last: [i64; 3],
+) -> ([i64; 3], Predictor) {
+    if let Some([a, b, c]) = prediction
+        && let (Some(a), Some(b), Some(c)) = (q[a], q[b], q[c])
+    {
+        (
+            std::array::from_fn(|i| a[i] + b[i] - c[i]),
+            Predictor::Parallelogram,
+        )
+    } else {
+        (last, Predictor::Delta)
+    }
+}
+
+/// Parallelogram predictor `a + b - c` of every vertex of `faces`. A vertex
+/// is predicted from the first face it appears in and the face across the
+/// opposite edge, if that one came earlier and no other face of the same
+/// component uses the edge. Components start at the faces in `starts`. The
+/// encoder finds the same predictions while it traverses the mesh.
+pub fn predictions(
+    faces: &[[usize; 3]],
+    vertex_count: usize,
+    starts: &[usize],
+) -> Vec<Option<[usize; 3]>> {
+    let mut predictions = vec![None; vertex_count];
+    let mut seen = vec![false; vertex_count];
+    let mut across: HashMap<(usize, usize), usize> = HashMap::new();
+
+    for range in chunks(starts, faces.len()) {
+        let crowded = crowded_edges(&faces[range.clone()]);
+        for f in &faces[range] {
+            for k in 0..3 {
+                let (v, a, b) = (f[k], f[(k + 1) % 3], f[(k + 2) % 3]);
+                if !seen[v]
+                    && !crowded.contains(&(a.min(b), a.max(b)))
+                    && let Some(&c) = across.get(&(b, a))
+                {
+                    predictions[v] = Some([a, b, c]);
+                }
+            }
+            for k in 0..3 {
+                seen[f[k]] = true;
+                across.insert((f[(k + 1) % 3], f[(k + 2) % 3]), f[k]);
+            }
+        }
+    }
+
+    predictions
+}
+
+/// Edges used by more than two of `faces`, smaller vertex first
+pub fn crowded_edges(faces: &[[usize; 3]]) -> HashSet<(usize, usize)> {
+    let mut uses = HashMap::new();
+    for f in faces {
+        for k in 0..3 {
+            let (a, b) = (f[k], f[(k + 1) % 3]);
+            *uses.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+    uses.into_iter()
+        .filter(|&(_, n)| n > 2)
+        .map(|(edge, _)| edge)
+        .collect()
+}
+
+/// Quantize `vertices` to `bits` and encode them in order. `predictions`
+/// holds the predictor of every vertex, see [`predictions`]. Coding starts
+/// over at every vertex in `starts` and only predicts from vertices coded
+/// since then. Returns the byte offset of each start.
+pub fn encode(
+    bits: u8,
+    vertices: &[[f32; 3]],
+    predictions: &[Option<[usize; 3]>],
+    starts: &[usize],
+) -> (QuantizedGeometry, Vec<usize>) {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for v in vertices {
+        for i in 0..3 {
+            min[i] = min[i].min(v[i]);
+            max[i] = max[i].max(v[i]);
+        }
+    }
+    if vertices.is_empty() {
+        min = [0.0; 3];
+        max = [0.0; 3];
+    }
+
+    let quantizer = Quantizer::new(bits, min, max);
+    let mut q = vec![None; vertices.len()];
+    let mut data = Vec::new();
+    let mut offsets = Vec::with_capacity(starts.len());
+
+    for range in chunks(starts, vertices.len()) {
+        let mut models = Models::new();
+        let mut encoder = Encoder::new();
+        let mut last = [0; 3];
+        for v in range.clone() {
+            let value = quantizer.quantize(vertices[v]);
+            let prediction = predictions[v].filter(|p| p.iter().all(|u| range.contains(u)));
+            let (pred, predictor) = predict(&q, prediction, last);
+            for i in 0..3 {
+                let residual = value[i] - pred[i];
+                let magnitude = residual.unsigned_abs();
+                let class = (u64::BITS - magnitude.leading_zeros()) as usize;
+                encoder.encode(&mut models.classes[predictor as usize][i], class);
+                if class > 1 {
+                    encoder.encode_bits(magnitude, class as u32 - 1);
+                }
+                if class > 0 {
+                    encoder.encode_bits((residual < 0) as u64, 1);
+                }
+            }
+
+            q[v] = Some(value);
+            last = value;
+        }
+        offsets.push(data.len());
+        data.extend(encoder.finish());
+    }
+
+    let geometry = QuantizedGeometry {
+        bits,
+        min,
+        max,
+        count: vertices.len(),
+        data,
+    };
+    (geometry, offsets)
+}
+
+/// Decode the positions of `geometry` that `chunks` cover, each given as its
+/// vertices and the byte offset their coding starts at. Vertices are decoded
+/// in index order and `predictions` holds the predictor of each of them, the
+/// others are left at the origin.
+pub fn decode(
+    geometry: &QuantizedGeometry,
+    predictions: &[Option<[usize; 3]>],
+    chunks: &[(Range<usize>, usize)],
+) -> Result<Vec<[f32; 3]>, EdgeBreakerError> {
+    if geometry.bits == 0 || geometry.bits > 32 {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "invalid quantization of {} bits",
+            geometry.bits
+        )));
+    }
+    if predictions.len() != geometry.count {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "quantized geometry has {} vertices, but the history {}",
+            geometry.count,
+            predictions.len()
+        )));
+    }
+
+    let quantizer = Quantizer::new(geometry.bits, geometry.min, geometry.max);
+    let mut q = vec![None; geometry.count];
+
+    for (range, offset) in chunks {
+        let (Some(data), true) = (geometry.data.get(*offset..), range.end <= geometry.count) else {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "quantized vertices {range:?} at byte {offset} lie outside of the geometry"
+            )));
+        };
+        let mut models = Models::new();
+        let mut decoder = Decoder::new(data);
+        let mut last = [0; 3];
+        for v in range.clone() {
+            let prediction = predictions[v].filter(|p| p.iter().all(|u| range.contains(u)));
+            let (pred, predictor) = predict(&q, prediction, last);
+            let mut value = [0; 3];
+            for i in 0..3 {
+                let class = decoder.decode(&mut models.classes[predictor as usize][i])?;
+                let mut magnitude = 0;
+                if class > 0 {
+                    magnitude = 1 << (class - 1);
+                }
+                if class > 1 {
+                    magnitude |= decoder.decode_bits(class as u32 - 1)? as i64;
+                }
+                let negative = class > 0 && decoder.decode_bits(1)? == 1;
+                value[i] = pred[i] + if negative { -magnitude } else { magnitude };
+            }
+
+            q[v] = Some(value);
+            last = value;
+        }
+    }
+
+    Ok(q.into_iter()
+        .map(|v| v.map_or([0.0; 3], |v| quantizer.dequantize(v)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompressOptions, Mesh, compress_with, decompress};
+
+    #[test]
+    fn quantized_roundtrip() {
+        // Bumpy 5x5 grid
+        let mut mesh = Mesh::default();
+        for i in 0..25 {
+            let (x, y) = ((i % 5) as f32, (i / 5) as f32);
+            mesh.vertices.push([x, y, (x * 0.7).sin() * y * 0.3]);
+        }
+        for j in 0..4 {
+            for i in 0..4 {
+                let a = j * 5 + i;
+                mesh.faces.push([a, a + 1, a + 6]);
+                mesh.faces.push([a, a + 6, a + 5]);
+            }
+        }
+
+        let options = CompressOptions {
+            quantization: Some(12),
+            ..Default::default()
+        };
+        let compressed = compress_with(&mesh, &options).unwrap();
+        assert!(compressed.vertices.is_empty());
+        let decoded = decompress(&compressed).unwrap();
+        assert_eq!(decoded.faces.len(), mesh.faces.len());
+
+        // Every decoded triangle must match an original one up to the
+        // quantization error
+        let tolerance = 4.0 / ((1 << 12) - 1) as f32;
+        let close = |a: [f32; 3], b: [f32; 3]| (0..3).all(|i| (a[i] - b[i]).abs() <= tolerance);
+        for f in &decoded.faces {
+            let positions = f.map(|v| decoded.vertices[v]);
+            assert!(mesh.faces.iter().any(|g| {
+                (0..3).any(|r| (0..3).all(|k| close(positions[k], mesh.vertices[g[(k + r) % 3]])))
+            }));
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// ,---------------------------------------------------------------------------
+// | Manifold: cutting a mesh into manifold pieces
+// '---------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+/// Faces of a mesh cut along its non-manifold edges and vertices. Each edge
+/// keeps at most one pair of opposite half-edges and each vertex a single
+/// fan of faces. The encoder maps the copies back to the input vertices
+/// through the duplicate list, which stitches the pieces together again.
+pub struct Cut {
+    pub faces: Vec<[usize; 3]>,
+    /// Opposite of half-edge `3 * f + k`, which runs from `faces[f][k]` to
+    /// the next corner of the face
+    pub opposite: Vec<Option<usize>>,
+    /// Input vertex of every vertex of the cut mesh. Vertices keep their
+    /// index for their first fan, copies for the others come after them.
+    pub original: Vec<usize>,
+    /// Half-edges left unpaired because their edge already has a pair, or
+    /// only other half-edges running in the same direction
+    pub conflicts: usize,
+}
+
+fn find(parent: &mut [usize], mut c: usize) -> usize {
+    while parent[c] != c {
+        parent[c] = parent[parent[c]];
+        c = parent[c];
+    }
+    c
+}
+
+fn next(h: usize) -> usize {
+    h - h % 3 + (h + 1) % 3
+}
+
+/// Call `f` with the half-edges of every undirected edge of `faces`, in
+/// increasing order. Half-edges are bucketed by their smaller vertex with a
+/// counting sort and every bucket is sorted by the larger one, which is much
+/// faster than hashing the edges of large meshes.
+pub fn for_each_edge<F: FnMut(&[usize])>(faces: &[[usize; 3]], vertex_count: usize, mut f: F) {
+    let key = |h: usize| {
+        let (a, b) = (faces[h / 3][h % 3], faces[h / 3][(h + 1) % 3]);
+        (a.min(b), a.max(b))
+    };
+
+    let mut offsets = vec![0; vertex_count + 1];
+    for h in 0..faces.len() * 3 {
+        offsets[key(h).0 + 1] += 1;
+    }
+    for v in 0..vertex_count {
+        offsets[v + 1] += offsets[v];
+    }
+    let mut buckets = vec![(0, 0); faces.len() * 3];
+    let mut fill = offsets.clone();
+    for h in 0..faces.len() * 3 {
+        let (a, b) = key(h);
+        buckets[fill[a]] = (b, h);
+        fill[a] += 1;
+    }
+
+    let mut group = Vec::new();
+    for v in 0..vertex_count {
+        let bucket = &mut buckets[offsets[v]..offsets[v + 1]];
+        bucket.sort_unstable();
+        for run in bucket.chunk_by(|x, y| x.0 == y.0) {
+            group.clear();
+            group.extend(run.iter().map(|&(_, h)| h));
+            f(&group);
+        }
+    }
+}
+
+/// Pair the first half-edge of every edge with the first one running in the
+/// opposite direction. Returns the opposites and the unpaired half-edges.
+pub fn pair(faces: &[[usize; 3]], vertex_count: usize) -> (Vec<Option<usize>>, usize) {
+    let start = |h: usize| faces[h / 3][h % 3];
+    let mut opposite = vec![None; faces.len() * 3];
+    let mut conflicts = 0;
+    for_each_edge(faces, vertex_count, |hs| {
+        let g = hs[0];
+        match hs[1..].iter().find(|&&h| start(h) == start(next(g))) {
+            Some(&h) => {
+                opposite[g] = Some(h);
+                opposite[h] = Some(g);
+                conflicts += hs.len() - 2;
+            }
+            None => conflicts += hs.len() - 1,
+        }
+    });
+    (opposite, conflicts)
+}
+
+pub fn cut(faces: &[[usize; 3]], vertex_count: usize) -> Cut {
+    let start = |h: usize| faces[h / 3][h % 3];
+    let (opposite, conflicts) = pair(faces, vertex_count);
+
+    // Corners around a vertex belong to the same fan when they are joined
+    // by paired edges. Half-edge h doubles as the corner it starts at.
+    let mut parent = (0..faces.len() * 3).collect::<Vec<_>>();
+    for (h, g) in opposite.iter().enumerate() {
+        if let Some(g) = g.filter(|&g| g < h) {
+            for (x, y) in [(h, next(g)), (next(h), g)] {
+                let (x, y) = (find(&mut parent, x), find(&mut parent, y));
+                parent[y] = x;
+            }
+        }
+    }
+
+    let mut original = (0..vertex_count).collect::<Vec<_>>();
+    let mut used = vec![false; vertex_count];
+    let mut fan_vertex = vec![None; faces.len() * 3];
+    let mut cut_faces = faces.to_vec();
+    for c in 0..faces.len() * 3 {
+        let root = find(&mut parent, c);
+        let v = start(c);
+        let w = *fan_vertex[root].get_or_insert_with(|| {
+            if used[v] {
+                original.push(v);
+                original.len() - 1
+            } else {
+                used[v] = true;
+                v
+            }
+        });
+        cut_faces[c / 3][c % 3] = w;
+    }
+
+    Cut {
+        faces: cut_faces,
+        opposite,
+        original,
+        conflicts,
+    }
+}
+
+/// Split `cut` into its edge connected components, ordered by their first
+/// face. Every piece numbers its faces and vertices from zero and maps its
+/// vertices to those of `cut` through `original`.
+pub fn split(cut: &Cut) -> Vec<Cut> {
+    let mut piece_of = vec![usize::MAX; cut.faces.len()];
+    let mut pieces = Vec::new();
+    let mut local = vec![usize::MAX; cut.original.len()];
+    for seed in 0..cut.faces.len() {
+        if piece_of[seed] != usize::MAX {
+            continue;
+        }
+        piece_of[seed] = pieces.len();
+        let mut faces = vec![seed];
+        let mut i = 0;
+        while i < faces.len() {
+            let f = faces[i];
+            i += 1;
+            for g in cut.opposite[3 * f..3 * f + 3].iter().flatten() {
+                if piece_of[g / 3] == usize::MAX {
+                    piece_of[g / 3] = pieces.len();
+                    faces.push(g / 3);
+                }
+            }
+        }
+        faces.sort_unstable();
+
+        let mut piece = Cut {
+            faces: Vec::with_capacity(faces.len()),
+            opposite: Vec::with_capacity(faces.len() * 3),
+            original: Vec::new(),
+            conflicts: 0,
+        };
+        let mut index = HashMap::with_capacity(faces.len());
+        for (t, &f) in faces.iter().enumerate() {
+            index.insert(f, t);
+        }
+        for &f in &faces {
+            piece.faces.push(cut.faces[f].map(|v| {
+                if local[v] == usize::MAX {
+                    local[v] = piece.original.len();
+                    piece.original.push(v);
+                }
+                local[v]
+            }));
+            for g in &cut.opposite[3 * f..3 * f + 3] {
+                piece.opposite.push(g.map(|g| 3 * index[&(g / 3)] + g % 3));
+            }
+        }
+        pieces.push(piece);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, hash_map::Entry};
+
+    use super::*;
+    use crate::{
+        CompressOptions, Mesh,
+        edgebreaker::meshes::{holes, random_grid, tube},
+        verify,
+    };
+
+    // Pairing as it was done before the sort-based grouping
+    fn pair_hashed(faces: &[[usize; 3]]) -> (Vec<Option<usize>>, usize) {
+        let start = |h: usize| faces[h / 3][h % 3];
+        let mut opposite = vec![None; faces.len() * 3];
+        let mut edges: HashMap<(usize, usize), (usize, bool)> = HashMap::new();
+        let mut conflicts = 0;
+        for h in 0..faces.len() * 3 {
+            let (a, b) = (start(h), start(next(h)));
+            match edges.entry((a.min(b), a.max(b))) {
+                Entry::Vacant(entry) => {
+                    entry.insert((h, false));
+                }
+                Entry::Occupied(mut entry) => match *entry.get() {
+                    (g, false) if start(g) == b => {
+                        opposite[g] = Some(h);
+                        opposite[h] = Some(g);
+                        entry.insert((g, true));
+                    }
+                    _ => conflicts += 1,
+                },
+            }
+        }
+        (opposite, conflicts)
+    }
+
+    #[test]
+    fn sorted_pairing_matches_hashed() {
+        let mut faces = random_grid(20, 1).faces;
+        // Fins and a face wound against its neighbours
+        faces.extend([[0, 1, 30], [1, 0, 31], [0, 1, 32], [22, 21, 0]]);
+        assert_eq!(pair(&faces, 441), pair_hashed(&faces));
+    }
+
+    #[test]
+    fn cut_separates_fans_and_fins() {
+        // Three faces on the edge 0-1: the third is cut off at both ends
+        let fin = cut(&[[0, 1, 2], [1, 0, 3], [0, 1, 4]], 5);
+        assert_eq!(fin.conflicts, 1);
+        assert_eq!(fin.faces[2], [5, 6, 4]);
+        assert_eq!(fin.original, [0, 1, 2, 3, 4, 0, 1]);
+        assert_eq!(fin.opposite[2 * 3], None);
+
+        // Strip whose ends touch at vertex 0
+        let pinched = Mesh {
+            vertices: (0..6).map(|i| [i as f32, (i % 2) as f32, 0.0]).collect(),
+            faces: vec![[0, 1, 2], [1, 3, 2], [2, 3, 4], [3, 5, 4], [4, 5, 0]],
+            ..Default::default()
+        };
+        let strip = cut(&pinched.faces, 6);
+        assert_eq!(strip.original, [0, 1, 2, 3, 4, 5, 0]);
+
+        // Two tetrahedra sharing vertex 0 become two closed pieces
+        let tet = [[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]];
+        let tets = Mesh {
+            vertices: (0..7).map(|i| [i as f32, (i * i) as f32, 0.0]).collect(),
+            faces: tet
+                .iter()
+                .chain(&tet.map(|f| f.map(|v| [0, 4, 5, 6][v])))
+                .copied()
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(cut(&tets.faces, 7).original.len(), 8);
+
+        for mesh in [pinched, tets] {
+            let report = verify(&mesh, &CompressOptions::default()).unwrap();
+            assert!(report.is_ok(), "{report}");
+        }
+    }
+
+    #[test]
+    fn cut_pieces_compress() {
+        // Soup whose largest piece after the cut is a sphere with three
+        // holes, where an S splits the boundary at a vertex of two loops
+        let soup = Mesh {
+            vertices: (0..13)
+                .map(|i| [i as f32, (i * i % 7) as f32, 0.0])
+                .collect(),
+            faces: vec![
+                [10, 6, 11],
+                [0, 1, 2],
+                [11, 5, 12],
+                [12, 6, 7],
+                [10, 11, 4],
+                [11, 12, 3],
+                [4, 7, 11],
+                [12, 10, 5],
+                [5, 11, 6],
+                [8, 6, 10],
+                [10, 5, 7],
+                [11, 10, 7],
+                [6, 7, 8],
+                [7, 4, 8],
+                [6, 9, 5],
+            ],
+            ..Default::default()
+        };
+        let report = verify(&soup, &CompressOptions::default()).unwrap();
+        assert!(report.is_ok(), "{report}");
+    }
+
+    #[test]
+    fn pinched_holes_round_trip() {
+        // Torus without faces [0, 1, 5] and [5, 6, 10], whose holes touch
+        // at vertex 5. Each fan of vertex 5 gets its own copy, and the
+        // handle of the torus needs an M.
+        let mut torus = tube(4, 4, true);
+        torus.faces.remove(10);
+        torus.faces.remove(0);
+        let report = verify(&torus, &CompressOptions::default()).unwrap();
+        assert!(report.is_ok(), "{report}");
+    }
+
+    #[test]
+    fn tori_with_holes_round_trip() {
+        // The holes at (4, 1) and (5, 2) touch at a vertex. Marking the loop
+        // of the merge on this torus used to go around without end.
+        let torus = holes(tube(6, 5, true), 6, &[(0, 0), (4, 1), (5, 2)]);
+        let report = verify(&torus, &CompressOptions::default()).unwrap();
+        assert!(report.is_ok(), "{report}");
+    }
+}
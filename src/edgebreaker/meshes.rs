@@ -0,0 +1,67 @@
+// ,---------------------------------------------------------------------------
+// | Test meshes shared by the encoder and decoder tests
+// '---------------------------------------------------------------------------
+
+use crate::Mesh;
+
+// Grid of n x n quads in the z = 0 plane, each split along the same diagonal
+pub fn grid(n: usize) -> Mesh {
+    diagonals(n, None)
+}
+
+// Grid whose diagonals are picked by a linear congruential generator started
+// at `seed`, which needs plenty of S
+pub fn random_grid(n: usize, seed: u32) -> Mesh {
+    diagonals(n, Some(seed))
+}
+
+fn diagonals(n: usize, seed: Option<u32>) -> Mesh {
+    let mut mesh = Mesh::default();
+    for i in 0..(n + 1) * (n + 1) {
+        mesh.vertices
+            .push([(i % (n + 1)) as f32, (i / (n + 1)) as f32, 0.0]);
+    }
+    let mut state = seed;
+    for j in 0..n {
+        for i in 0..n {
+            let a = j * (n + 1) + i;
+            let (b, c, d) = (a + 1, a + n + 2, a + n + 1);
+            state = state.map(|s| s.wrapping_mul(1664525).wrapping_add(1013904223));
+            if state.is_none_or(|s| s >> 31 == 0) {
+                mesh.faces.extend([[a, b, c], [a, c, d]]);
+            } else {
+                mesh.faces.extend([[a, b, d], [b, c, d]]);
+            }
+        }
+    }
+    mesh
+}
+
+// Grid or tube with the two triangles of every listed cell removed
+pub fn holes(mut grid: Mesh, n: usize, cells: &[(usize, usize)]) -> Mesh {
+    let mut cells = cells.to_vec();
+    cells.sort_by_key(|&(i, j)| j * n + i);
+    for &(i, j) in cells.iter().rev() {
+        let k = 2 * (j * n + i);
+        grid.faces.drain(k..k + 2);
+    }
+    grid
+}
+
+// Grid of n x m quads rolled into an open tube bounded by two loops, or
+// closed into a torus
+pub fn tube(n: usize, m: usize, closed: bool) -> Mesh {
+    let rows = if closed { m } else { m + 1 };
+    let mut mesh = Mesh::default();
+    for i in 0..n * rows {
+        mesh.vertices.push([(i % n) as f32, (i / n) as f32, 0.0]);
+    }
+    let at = |i: usize, j: usize| (j % rows) * n + i % n;
+    for j in 0..m {
+        for i in 0..n {
+            mesh.faces.push([at(i, j), at(i + 1, j), at(i + 1, j + 1)]);
+            mesh.faces.push([at(i, j), at(i + 1, j + 1), at(i, j + 1)]);
+        }
+    }
+    mesh
+}
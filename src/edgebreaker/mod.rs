@@ -1,144 +1,1547 @@
 #![allow(non_snake_case)]
 
+mod arithmetic;
+mod attributes;
 mod common;
 mod compression;
 mod decompression;
+mod geometry;
+pub mod manifold;
+#[cfg(test)]
+mod meshes;
+pub(crate) mod orientation;
+mod polygons;
 pub mod public;
+mod spirale;
+mod wrapzip;
 
-use crate::obj::{Obj, Table};
+use crate::{EdgeBreakerError, Mesh, VertexProperty, obj::Obj, weld};
 use common::{EdgeBreaker, Id, NULL};
-use compression::{HalfEdges, compress};
+use compression::{HalfEdges, Visit, compress};
 use decompression::decompress;
-use log::debug;
-use public::Op;
-
-fn table_scount(entry: Table) -> isize {
-    match entry {
-        Table::Hole(_s, _) => _s as isize,
-        Table::Merge(_s, _, _, _) => _s as isize,
-    }
-}
+use log::{debug, warn};
+use public::{
+    ComponentEntry, CompressOptions, Compressed, DecoderKind, DecompressOptions, HistoryEncoding,
+    Op, Table,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Instant,
+};
 
 // ,---------------------------------------------------------------------------
 // | Public functions
 // '---------------------------------------------------------------------------
 
-pub fn compress_obj(obj: &mut Obj) {
-    let mut he = HalfEdges::init(obj);
-    let eb = compress(&mut he);
+pub fn compress_mesh(
+    mesh: &Mesh,
+    options: &CompressOptions,
+) -> Result<Compressed, EdgeBreakerError> {
+    encode_mesh(mesh, options).map(|encoded| encoded.compressed)
+}
+
+/// Output of [`encode_mesh`]: the compressed mesh and what the encoder found
+/// out about the input along the way.
+pub struct Encoded {
+    pub compressed: Compressed,
+    /// Index in `mesh.vertices` of every compressed vertex
+    pub order: Vec<usize>,
+    /// Number of half-edges that could not be paired because their edge is
+    /// already shared by two triangles, or only by triangles with the same
+    /// winding
+    pub conflicts: usize,
+    /// Number of vertices no face references, stored after all others
+    pub isolated: usize,
+    /// Input vertex that every input vertex was welded into, empty without
+    /// [`CompressOptions::weld`]
+    pub welds: Vec<usize>,
+}
+
+/// Like [`compress_mesh`], but keeps the encoder's bookkeeping around.
+pub fn encode_mesh(mesh: &Mesh, options: &CompressOptions) -> Result<Encoded, EdgeBreakerError> {
+    if let Some(f) = mesh
+        .faces
+        .iter()
+        .find(|f| f.iter().any(|&v| v >= mesh.vertices.len()))
+    {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "face {:?} references a vertex out of range",
+            f
+        )));
+    }
+    if let Some(f) = mesh
+        .faces
+        .iter()
+        .find(|f| f[0] == f[1] || f[1] == f[2] || f[2] == f[0])
+    {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "face {:?} references a vertex twice",
+            f
+        )));
+    }
+
+    for (name, corners, count) in [
+        (
+            "texture coordinate",
+            &mesh.face_texcoords,
+            mesh.texcoords.len(),
+        ),
+        ("normal", &mesh.face_normals, mesh.normals.len()),
+    ] {
+        if !corners.is_empty() && corners.len() != mesh.faces.len() {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "{} faces have a {name} index, but the mesh has {} faces",
+                corners.len(),
+                mesh.faces.len()
+            )));
+        }
+        if let Some(c) = corners.iter().find(|c| c.iter().any(|&i| i >= count)) {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "corner {name}s {c:?} reference a value out of range"
+            )));
+        }
+    }
+
+    if let Some(p) = mesh
+        .properties
+        .iter()
+        .find(|p| p.values.len() != mesh.vertices.len())
+    {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "vertex property '{}' has {} values for {} vertices",
+            p.name,
+            p.values.len(),
+            mesh.vertices.len()
+        )));
+    }
+
+    if let Some(&n) = mesh.polygons.iter().find(|&&n| n < 3) {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "polygon with {n} corners"
+        )));
+    }
+    let fanned = mesh.polygons.iter().map(|n| n - 2).sum::<usize>();
+    if !mesh.polygons.is_empty() && fanned != mesh.faces.len() {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "polygons make up {fanned} faces, but the mesh has {} faces",
+            mesh.faces.len()
+        )));
+    }
+
+    if let Some(bits) = options.quantization
+        && !(1..=32).contains(&bits)
+    {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "cannot quantize to {bits} bits, expected 1 to 32"
+        )));
+    }
+
+    // Weld duplicate positions, otherwise the faces around them do not
+    // connect. Welded vertices stay in place without faces.
+    let welds = match options.weld {
+        Some(tolerance) => weld::representatives(&mesh.vertices, tolerance),
+        None => Vec::new(),
+    };
+    let faces = match options.weld {
+        Some(_) => Cow::Owned(weld::remap_faces(&mesh.faces, &welds)),
+        None => Cow::Borrowed(&mesh.faces[..]),
+    };
+    debug!(
+        "Welded {} vertices",
+        welds.iter().enumerate().filter(|&(p, &w)| p != w).count()
+    );
+
+    // Make the winding consistent, otherwise flipped faces look non-manifold
+    let flips = orientation::orient(&faces);
+    let orient = |corners: &[[usize; 3]]| {
+        corners
+            .iter()
+            .zip(&flips)
+            .map(|(&[a, b, c], &flip)| if flip { [a, c, b] } else { [a, b, c] })
+            .collect::<Vec<_>>()
+    };
+    let oriented = orient(&faces);
+
+    // Polygon of every face. Polygons whose faces are not a fan around their
+    // first corner, that use a vertex twice or whose faces do not agree on
+    // the winding are kept as separate triangles.
+    let keep_polygons = !options.triangulate && mesh.polygons.iter().any(|&n| n > 3);
+    let mut polygon_of = Vec::new();
+    if keep_polygons {
+        let mut t = 0;
+        for (i, &n) in mesh.polygons.iter().enumerate() {
+            let fan = &faces[t..t + n - 2];
+            let mut corners = vec![fan[0][0], fan[0][1]];
+            corners.extend(fan.iter().map(|f| f[2]));
+            corners.sort();
+            corners.dedup();
+            if corners.len() == n
+                && fan.iter().all(|f| f[0] == fan[0][0])
+                && fan.windows(2).all(|w| w[0][2] == w[1][1])
+                && flips[t..t + n - 2].iter().all(|&f| f == flips[t])
+            {
+                polygon_of.extend(std::iter::repeat_n(i, n - 2));
+            } else {
+                warn!("polygon {i} is not a simple fan, its triangles are kept apart");
+                polygon_of.extend((t..t + n - 2).map(|f| mesh.polygons.len() + f));
+            }
+            t += n - 2;
+        }
+    }
+
+    // Compress the manifold pieces, their copies of a vertex end up in dup
+    let cut = manifold::cut(&oriented, mesh.vertices.len());
+    let mut components = Vec::new();
+    let crowding = options.quantization.is_some() && cut.conflicts > 0;
+    let (mut eb, mut visits) = if options.threads == 0 {
+        let (eb, mut visits) = compress(&mut HalfEdges::init(&cut))?;
+        if crowding {
+            forget_crowded(&mut visits, &cut, |v| cut.original[v]);
+        }
+        (eb, visits)
+    } else {
+        // Connected components are independent, their histories follow
+        // each other in the order of their first face
+        let pieces = manifold::split(&cut);
+        let start = Instant::now();
+        let results = parallel_map(&pieces, options.threads, |piece| {
+            let (mut eb, mut visits) = compress(&mut HalfEdges::init(piece))?;
+            if crowding {
+                forget_crowded(&mut visits, piece, |v| cut.original[piece.original[v]]);
+            }
+            let original = |v: &mut Id| *v = Id::from_offset(piece.original[v.offset()]);
+            eb.previous.iter_mut().for_each(original);
+            for (v, prediction) in visits.iter_mut() {
+                original(v);
+                prediction.iter_mut().flatten().for_each(original);
+            }
+            Ok((eb, visits))
+        })?;
+        debug!(
+            "Compressed {} components on {} threads in {:?}",
+            pieces.len(),
+            options.threads,
+            start.elapsed()
+        );
+        let mut eb = EdgeBreaker {
+            history: Vec::new(),
+            previous: Vec::new(),
+            lengths: Vec::new(),
+            m_table: Vec::new(),
+        };
+        let mut visits = Vec::new();
+        for (piece, piece_visits) in results {
+            components.push(ComponentEntry {
+                faces: piece.history.len(),
+                vertices: piece.previous.len(),
+                history_bit: 0,
+                table: 0,
+                dup: 0,
+                vertex_range: 0..0,
+                geometry: 0,
+                texcoords: (0, 0),
+                normals: (0, 0),
+                polygons: 0,
+            });
+            eb.history.extend(piece.history);
+            eb.previous.extend(piece.previous);
+            eb.lengths.extend(piece.lengths);
+            eb.m_table.extend(piece.m_table);
+            visits.extend(piece_visits);
+        }
+        (eb, visits)
+    };
+    let original = |v: &mut Id| *v = Id::from_offset(cut.original[v.offset()]);
+    eb.previous.iter_mut().for_each(original);
+    for (v, prediction) in visits.iter_mut() {
+        original(v);
+        prediction.iter_mut().flatten().for_each(original);
+    }
+    let mut encoding = options.encoding;
+    if encoding == HistoryEncoding::Conditioned
+        && let Some(w) = eb
+            .history
+            .windows(2)
+            .find(|w| w[0] == Op::C && matches!(w[1], Op::L | Op::E))
+    {
+        warn!(
+            "{:?}{:?} in the history cannot be written with the conditioned code, using the prefix code",
+            w[0], w[1]
+        );
+        encoding = HistoryEncoding::Prefix;
+    }
     debug!("eb: {:?}", eb);
     debug!("History: {:?}", eb.history);
     debug!("Previous: {:?}", eb.previous);
     debug!("Lengths: {:?}", eb.lengths);
 
-    let mut perm_vertices = Vec::with_capacity(obj.vertices.len());
+    let mut perm_vertices = Vec::with_capacity(mesh.vertices.len());
+    let mut order = Vec::with_capacity(mesh.vertices.len());
+    let mut properties = mesh
+        .properties
+        .iter()
+        .map(|p| VertexProperty {
+            name: p.name.clone(),
+            kind: p.kind,
+            values: Vec::with_capacity(p.values.len()),
+        })
+        .collect::<Vec<_>>();
     let mut dup = Vec::new();
-    let mut inserted = vec![NULL; obj.vertices.len()];
-    let mut c = 0;
-    for p in eb.previous {
+    let mut inserted = vec![NULL; mesh.vertices.len()];
+    for (c, &p) in eb.previous.iter().enumerate() {
         if inserted[p] == NULL {
-            inserted[p] = Id::from_offset(c);
-            perm_vertices.push(obj.vertices[p]);
+            inserted[p] = Id::from_offset(perm_vertices.len());
+            perm_vertices.push(mesh.vertices[p]);
+            order.push(p.offset());
+            for (permuted, property) in properties.iter_mut().zip(&mesh.properties) {
+                permuted.values.push(property.values[p]);
+            }
         } else {
             dup.push((c, inserted[p].offset()));
         }
-        c += 1;
     }
 
-    // eb_table is more involved
-    let mut s_count = 0;
-    let mut h = 0;
-    let mut m = 0;
-    let mut eb_table = Vec::new();
+    // Vertices no face references follow in input order. Welded ones are
+    // dropped unless they are restored.
+    let referenced = perm_vertices.len();
+    for (p, id) in inserted.iter_mut().enumerate() {
+        if *id == NULL && (options.restore_welded || welds.get(p).is_none_or(|&w| w == p)) {
+            *id = Id::from_offset(perm_vertices.len());
+            perm_vertices.push(mesh.vertices[p]);
+            order.push(p);
+            for (permuted, property) in properties.iter_mut().zip(&mesh.properties) {
+                permuted.values.push(property.values[p]);
+            }
+        }
+    }
+
+    // Index the components for random access
+    locate(&mut components, &eb.history, &dup);
+    let (_, offsets) = encoding.encode_components(&eb.history, &components);
+    for (c, offset) in components.iter_mut().zip(offsets) {
+        c.history_bit = offset;
+    }
+
+    // Parallelogram predictions from the traversal. A vertex split by the
+    // cut is predicted where the first of its copies is visited.
+    let mut geometry = None;
+    if let Some(bits) = options.quantization {
+        let mut predictions = vec![None; perm_vertices.len()];
+        let mut predicted = vec![false; perm_vertices.len()];
+        for (v, prediction) in visits {
+            let v = inserted[v].offset();
+            if !predicted[v] {
+                predicted[v] = true;
+                predictions[v] = prediction.map(|p| p.map(|u| inserted[u].offset()));
+            }
+        }
+        let firsts = components.iter().map(|c| c.vertex_range.start);
+        let (coded, offsets) = geometry::encode(
+            bits,
+            &perm_vertices,
+            &predictions,
+            &firsts.collect::<Vec<_>>(),
+        );
+        for (c, offset) in components.iter_mut().zip(offsets) {
+            c.geometry = offset;
+        }
+        geometry = Some(coded);
+    }
+
+    // Replay the decoder to find the faces in the order they are decoded.
+    // The streams start over at every component so that each decodes alone.
+    // A history the decoder rejects means the traversal met a boundary it
+    // does not handle, and is not written at all.
+    let decoded = decompress(&eb).map_err(|e| {
+        EdgeBreakerError::UnsupportedTopology(format!("history does not decode: {e}"))
+    })?;
+    let has_attributes = !mesh.face_texcoords.is_empty() || !mesh.face_normals.is_empty();
+    let restore_flips = !options.fix_orientation && flips.contains(&true);
+    let restore_welds = options.restore_welded && *faces != mesh.faces[..];
+    let mut texcoords = None;
+    let mut normals = None;
+    let mut flipped = Vec::new();
+    let mut welded = Vec::new();
+    let mut polygons = None;
+    if has_attributes || restore_flips || restore_welds || keep_polygons {
+        let faces = decoded
+            .into_iter()
+            .map(|f| f.map(|v| inserted[v].offset()))
+            .collect::<Vec<_>>();
 
+        let starts = component_starts(&components);
+
+        let input = oriented.iter().map(|f| f.map(|v| inserted[v].offset()));
+        let matched = attributes::match_faces(&input.collect::<Vec<_>>(), &faces)?;
+        if restore_flips {
+            flipped = (0..faces.len()).filter(|&t| flips[matched[t].0]).collect();
+        }
+        if keep_polygons {
+            let decoded = matched.iter().map(|&(f, _)| polygon_of[f]);
+            let (coded, offsets) = polygons::encode(&faces, &decoded.collect::<Vec<_>>(), &starts);
+            for (c, offset) in components.iter_mut().zip(offsets) {
+                c.polygons = offset;
+            }
+            polygons = Some(coded);
+        }
+        let corners = |face_corners: &[[usize; 3]]| {
+            matched
+                .iter()
+                .map(|&(f, r)| std::array::from_fn(|k| face_corners[f][(k + r) % 3]))
+                .collect::<Vec<_>>()
+        };
+        if restore_welds {
+            let input = corners(&orient(&mesh.faces));
+            for (t, (f, g)) in faces.iter().zip(&input).enumerate() {
+                for k in 0..3 {
+                    let v = inserted[g[k]].offset();
+                    if v != f[k] {
+                        welded.push((3 * t + k, v));
+                    }
+                }
+            }
+        }
+        let n = perm_vertices.len();
+        if !mesh.face_texcoords.is_empty() {
+            let corners = corners(&orient(&mesh.face_texcoords));
+            let (coded, offsets) =
+                attributes::encode(&mesh.texcoords, &faces, &corners, n, &starts);
+            for (c, offset) in components.iter_mut().zip(offsets) {
+                c.texcoords = offset;
+            }
+            texcoords = Some(coded);
+        }
+        if !mesh.face_normals.is_empty() {
+            let corners = corners(&orient(&mesh.face_normals));
+            let (coded, offsets) = attributes::encode(&mesh.normals, &faces, &corners, n, &starts);
+            for (c, offset) in components.iter_mut().zip(offsets) {
+                c.normals = offset;
+            }
+            normals = Some(coded);
+        }
+    }
+
+    let mut lengths = eb.lengths.iter();
+    let mut merges = eb.m_table.iter();
+    let mut table = Vec::new();
     for op in &eb.history {
         match op {
-            Op::S => s_count += 1,
-            Op::H => {
-                let l = eb.lengths[h];
-                h += 1;
-                eb_table.push(Table::Hole(s_count, l));
-                s_count = 0;
-            }
+            Op::H => table.push(Table::Hole(*lengths.next().unwrap())),
             Op::M => {
-                let (i1, i2, i3) = eb.m_table[m];
-                m += 1;
-                eb_table.push(Table::Merge(s_count, i1, i2, i3));
-                s_count = 0;
+                let &(i1, i2, i3) = merges.next().unwrap();
+                table.push(Table::Merge(i1, i2, i3));
             }
             _ => {} // Do nothing
         }
     }
 
-    obj.vertices = perm_vertices;
-    obj.faces = Vec::new();
-    obj.eb_history = eb.history;
-    obj.eb_table = eb_table;
-    obj.eb_dup = dup;
+    if geometry.is_some() {
+        perm_vertices.clear();
+    }
+
+    let compressed = Compressed {
+        vertices: perm_vertices,
+        history: eb.history,
+        table,
+        dup,
+        encoding,
+        geometry,
+        texcoords,
+        normals,
+        properties,
+        flipped,
+        welded,
+        polygons,
+        components,
+    };
+    let mut used = vec![false; mesh.vertices.len()];
+    for &v in mesh.faces.iter().flatten() {
+        used[v] = true;
+    }
+    let isolated = order[referenced..].iter().filter(|&&p| !used[p]).count();
+    Ok(Encoded {
+        compressed,
+        order,
+        conflicts: cut.conflicts,
+        isolated,
+        welds,
+    })
 }
 
-pub fn decompress_obj(obj: &mut Obj) {
-    let mut history = Vec::with_capacity(obj.eb_history.len());
-    let mut lengths = Vec::new();
-    let mut m_table = Vec::new();
+// The decoder cannot tell which face lies across an edge that more than two
+// faces of a component use, so it predicts nothing over it. `input` maps the
+// vertices of `piece` to those of the mesh.
+fn forget_crowded(visits: &mut [Visit], piece: &manifold::Cut, input: impl Fn(usize) -> usize) {
+    let faces = piece.faces.iter().map(|f| f.map(&input));
+    let crowded = geometry::crowded_edges(&faces.collect::<Vec<_>>());
+    for (_, prediction) in visits.iter_mut() {
+        if let Some([a, b, _]) = *prediction {
+            let (a, b) = (input(a.offset()), input(b.offset()));
+            if crowded.contains(&(a.min(b), a.max(b))) {
+                *prediction = None;
+            }
+        }
+    }
+}
 
+// Items from every start up to the next one, all of them without any starts
+fn chunks(starts: &[usize], count: usize) -> Vec<Range<usize>> {
+    let starts = if starts.is_empty() { &[0] } else { starts };
+    (0..starts.len())
+        .map(|k| starts[k]..starts.get(k + 1).copied().unwrap_or(count))
+        .collect()
+}
+
+// First operation of every component
+fn component_starts(components: &[ComponentEntry]) -> Vec<usize> {
     let mut t = 0;
-    let mut s = obj.eb_table.first().map_or(-1, |&x| table_scount(x));
+    components
+        .iter()
+        .map(|c| {
+            t += c.faces;
+            t - c.faces
+        })
+        .collect()
+}
 
-    for op in &obj.eb_history {
-        match op {
-            Op::S | Op::M | Op::H => {
-                if s == 0 {
-                    match obj.eb_table[t] {
-                        Table::Hole(_, l) => {
-                            history.push(Op::H);
-                            lengths.push(l);
-                        }
-                        Table::Merge(_, i1, i2, i3) => {
-                            history.push(Op::M);
-                            m_table.push((i1, i2, i3));
-                        }
-                    }
-                    t += 1;
-                    s = obj.eb_table.get(t).map_or(-1, |&x| table_scount(x));
-                } else {
-                    history.push(*op);
-                    s -= 1;
-                }
-            }
+// Find where every component starts in the table, the duplicate list and the
+// vertices from the number of its faces and vertex labels
+fn locate(components: &mut [ComponentEntry], history: &[Op], dup: &[(usize, usize)]) {
+    let (mut t, mut label, mut table) = (0, 0, 0);
+    for c in components {
+        c.table = table;
+        c.dup = dup.partition_point(|&(pos, _)| pos < label);
+        let end = dup.partition_point(|&(pos, _)| pos < label + c.vertices);
+        c.vertex_range = label - c.dup..label + c.vertices - end;
+        let ops = &history[t..t + c.faces];
+        table += ops.iter().filter(|&&op| op == Op::H || op == Op::M).count();
+        t += c.faces;
+        label += c.vertices;
+    }
+}
+
+type SideInfo = (Vec<usize>, Vec<(usize, usize, usize)>);
+
+// Pull the hole lengths and merge entries of the H and M in `history` out of
+// the table
+fn side_info(history: &[Op], table: &[Table]) -> Result<SideInfo, EdgeBreakerError> {
+    let count = history
+        .iter()
+        .filter(|&&op| op == Op::H || op == Op::M)
+        .count();
+    if count != table.len() {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "history has {count} holes and merges, but the table {} entries",
+            table.len()
+        )));
+    }
+
+    let mut lengths = Vec::new();
+    let mut m_table = Vec::new();
+    let ops = history.iter().filter(|&&op| op == Op::H || op == Op::M);
+    for (t, (op, entry)) in ops.zip(table).enumerate() {
+        match (op, *entry) {
+            (Op::H, Table::Hole(l)) => lengths.push(l),
+            (Op::M, Table::Merge(i1, i2, i3)) => m_table.push((i1, i2, i3)),
             _ => {
-                history.push(*op);
+                return Err(EdgeBreakerError::TableMismatch(format!(
+                    "table entry {t} does not match the {op:?} it belongs to"
+                )));
             }
         }
     }
+    Ok((lengths, m_table))
+}
+
+// Expand the table and the duplicate list back into what the decoders use
+fn unpack(compressed: &Compressed) -> Result<EdgeBreaker, EdgeBreakerError> {
+    let faces = compressed.components.iter().map(|c| c.faces).sum::<usize>();
+    if !compressed.components.is_empty() && faces != compressed.history.len() {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "component directory covers {faces} faces, but the history has {}",
+            compressed.history.len()
+        )));
+    }
+    let history = compressed.history.clone();
+    let (lengths, m_table) = side_info(&history, &compressed.table)?;
 
     let mut previous = Vec::new();
     let mut i = 0;
-    for (pos, idx) in &obj.eb_dup {
+    for (pos, idx) in &compressed.dup {
+        if *pos < previous.len() {
+            return Err(EdgeBreakerError::TableMismatch(format!(
+                "duplicate entry {pos}/{idx} is out of order"
+            )));
+        }
         while previous.len() < *pos {
             previous.push(Id::from_offset(i));
             i += 1;
         }
+        if *idx >= i {
+            return Err(EdgeBreakerError::TableMismatch(format!(
+                "duplicate entry {pos}/{idx} references a vertex that was not emitted yet"
+            )));
+        }
         previous.push(Id::from_offset(*idx));
     }
 
-    for _ in 0..obj.vertices.len() - (previous.len() - obj.eb_dup.len()) {
+    let vertex_count = compressed.vertex_count();
+    let unique = previous.len() - compressed.dup.len();
+    if unique > vertex_count {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "duplicate list references {} vertices, but only {} are present",
+            unique, vertex_count
+        )));
+    }
+    if let Some(p) = compressed
+        .properties
+        .iter()
+        .find(|p| p.values.len() != vertex_count)
+    {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "vertex property '{}' has {} values for {} vertices",
+            p.name,
+            p.values.len(),
+            vertex_count
+        )));
+    }
+    for _ in 0..vertex_count - unique {
         previous.push(Id::from_offset(i));
         i += 1;
     }
 
+    if let Some(w) = compressed.flipped.windows(2).find(|w| w[0] >= w[1]) {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "flipped faces {} and {} are out of order",
+            w[0], w[1]
+        )));
+    }
+    if let Some(&f) = compressed.flipped.last()
+        && f >= history.len()
+    {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "flipped face {f} does not exist, the history has {} faces",
+            history.len()
+        )));
+    }
+
+    if let Some(w) = compressed.welded.windows(2).find(|w| w[0].0 >= w[1].0) {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "welded corners {} and {} are out of order",
+            w[0].0, w[1].0
+        )));
+    }
+    if let Some(&(c, v)) = compressed.welded.last()
+        && c >= 3 * history.len()
+    {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "welded corner {c}/{v} does not exist, the history has {} faces",
+            history.len()
+        )));
+    }
+    if let Some(&(c, v)) = compressed.welded.iter().find(|&&(_, v)| v >= vertex_count) {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "welded corner {c}/{v} references a vertex out of range"
+        )));
+    }
+
+    let labels = compressed
+        .components
+        .iter()
+        .map(|c| c.vertices)
+        .sum::<usize>();
+    if labels > previous.len() {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "component directory covers {labels} vertex labels, but there are only {}",
+            previous.len()
+        )));
+    }
+    let mut located = compressed.components.clone();
+    locate(&mut located, &history, &compressed.dup);
+    if let Some(i) = (0..located.len()).find(|&i| {
+        let (a, b) = (&located[i], &compressed.components[i]);
+        (a.table, a.dup, &a.vertex_range) != (b.table, b.dup, &b.vertex_range)
+    }) {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "component {i} does not start where the directory says"
+        )));
+    }
+
     let eb = EdgeBreaker {
-        history: history,
-        previous: previous,
-        lengths: lengths,
-        m_table: m_table,
+        history,
+        previous,
+        lengths,
+        m_table,
     };
     debug!("eb: {:?}", eb);
-    let faces = decompress(&eb);
+    Ok(eb)
+}
+
+// Run `f` on every item on up to `threads` threads. Results keep the order
+// of the items, the first error in that order is returned.
+fn parallel_map<T, R, F>(items: &[T], threads: usize, f: F) -> Result<Vec<R>, EdgeBreakerError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, EdgeBreakerError> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads.clamp(1, items.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(i) else {
+                            return done;
+                        };
+                        done.push((i, f(item)));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("worker thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|&(i, _)| i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+fn decode(eb: &EdgeBreaker, decoder: DecoderKind) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    match decoder {
+        DecoderKind::Forward => decompress(eb),
+        DecoderKind::SpiraleReversi => spirale::decompress(eb),
+        DecoderKind::WrapZip => wrapzip::decompress(eb),
+    }
+}
+
+// Cut `eb` along the component directory and decode the pieces in parallel.
+// Vertex labels are already global, so the faces only need concatenating.
+fn decode_components(
+    eb: &EdgeBreaker,
+    components: &[ComponentEntry],
+    options: &DecompressOptions,
+) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let mut pieces = Vec::with_capacity(components.len());
+    let (mut t, mut v, mut h, mut m) = (0, 0, 0, 0);
+    for c in components {
+        let history = eb.history[t..t + c.faces].to_vec();
+        let holes = history.iter().filter(|&&op| op == Op::H).count();
+        let merges = history.iter().filter(|&&op| op == Op::M).count();
+        let (Some(lengths), Some(m_table)) =
+            (eb.lengths.get(h..h + holes), eb.m_table.get(m..m + merges))
+        else {
+            return Err(EdgeBreakerError::TableMismatch(
+                "component directory does not match the table".to_string(),
+            ));
+        };
+        pieces.push(EdgeBreaker {
+            history,
+            previous: eb.previous[v..v + c.vertices].to_vec(),
+            lengths: lengths.to_vec(),
+            m_table: m_table.to_vec(),
+        });
+        (t, v, h, m) = (t + c.faces, v + c.vertices, h + holes, m + merges);
+    }
+
+    let faces = parallel_map(&pieces, options.threads, |piece| {
+        decode(piece, options.decoder)
+    })?;
+    Ok(faces.concat())
+}
+
+pub fn decompress_mesh(
+    compressed: &Compressed,
+    options: &DecompressOptions,
+) -> Result<Mesh, EdgeBreakerError> {
+    let eb = unpack(compressed)?;
+    let vertex_count = compressed.vertex_count();
+    let start = Instant::now();
+    let faces = if options.threads > 1 && !compressed.components.is_empty() {
+        decode_components(&eb, &compressed.components, options)?
+    } else {
+        decode(&eb, options.decoder)?
+    };
+    debug!(
+        "Decoded {} faces with the {} decoder in {:?}",
+        faces.len(),
+        options.decoder.name(),
+        start.elapsed()
+    );
     debug!("Faces: {:?}", faces);
     debug!("Faces len: {:?}", faces.len());
-    obj.eb_history = Vec::new();
-    obj.eb_table = Vec::new();
-    obj.eb_dup = Vec::new();
-    obj.faces = faces;
+
+    let chunks = stream_chunks(compressed);
+    let vertices = match &compressed.geometry {
+        Some(geometry) => {
+            let coded = chunks.iter().map(|c| (c.vertices.clone(), c.geometry));
+            let starts = chunks.iter().map(|c| c.faces.start);
+            let predictions =
+                geometry::predictions(&faces, vertex_count, &starts.collect::<Vec<_>>());
+            geometry::decode(geometry, &predictions, &coded.collect::<Vec<_>>())?
+        }
+        None => compressed.vertices.clone(),
+    };
+    decode_streams(compressed, faces, vertices, &chunks)
+}
+
+// Faces of a component, the vertices its geometry codes and where its streams
+// start. Without a directory the whole mesh is a single chunk.
+struct Chunk {
+    faces: Range<usize>,
+    vertices: Range<usize>,
+    geometry: usize,
+    texcoords: (usize, usize),
+    normals: (usize, usize),
+    polygons: usize,
+}
+
+// Streams start over at every component, and the geometry of one also codes
+// the vertices no face uses up to the next one
+fn stream_chunks(compressed: &Compressed) -> Vec<Chunk> {
+    let components = &compressed.components;
+    if components.is_empty() {
+        return vec![Chunk {
+            faces: 0..compressed.history.len(),
+            vertices: 0..compressed.vertex_count(),
+            geometry: 0,
+            texcoords: (0, 0),
+            normals: (0, 0),
+            polygons: 0,
+        }];
+    }
+    let starts = component_starts(components);
+    (0..components.len())
+        .map(|k| {
+            let c = &components[k];
+            let end = components
+                .get(k + 1)
+                .map_or(compressed.vertex_count(), |c| c.vertex_range.start);
+            Chunk {
+                faces: starts[k]..starts[k] + c.faces,
+                vertices: c.vertex_range.start..end,
+                geometry: c.geometry,
+                texcoords: c.texcoords,
+                normals: c.normals,
+                polygons: c.polygons,
+            }
+        })
+        .collect()
+}
+
+// Faces of every chunk, counted from the first one, and where `offset` says
+// its part of a stream starts
+fn runs<T>(chunks: &[Chunk], offset: impl Fn(&Chunk) -> T) -> Vec<(Range<usize>, T)> {
+    let first = chunks.first().map_or(0, |c| c.faces.start);
+    chunks
+        .iter()
+        .map(|c| (c.faces.start - first..c.faces.end - first, offset(c)))
+        .collect()
+}
+
+// Decode the attributes and polygons of `faces`, decoded for `chunks` that
+// follow each other, put back the welded vertices and the input winding
+fn decode_streams(
+    compressed: &Compressed,
+    mut faces: Vec<[usize; 3]>,
+    vertices: Vec<[f32; 3]>,
+    chunks: &[Chunk],
+) -> Result<Mesh, EdgeBreakerError> {
+    let vertex_count = compressed.vertex_count();
+    let mut mesh = Mesh {
+        vertices,
+        properties: compressed.properties.clone(),
+        ..Default::default()
+    };
+    if let Some(texcoords) = &compressed.texcoords {
+        let runs = runs(chunks, |c| c.texcoords);
+        mesh.face_texcoords = attributes::decode(texcoords, &faces, vertex_count, &runs)?;
+        mesh.texcoords = texcoords.values.clone();
+    }
+    if let Some(normals) = &compressed.normals {
+        let runs = runs(chunks, |c| c.normals);
+        mesh.face_normals = attributes::decode(normals, &faces, vertex_count, &runs)?;
+        mesh.normals = normals.values.clone();
+    }
+
+    // Diagonals are coded with the decoded winding
+    let groups = match &compressed.polygons {
+        Some(data) => Some(polygons::decode(
+            data,
+            &faces,
+            &runs(chunks, |c| c.polygons),
+        )?),
+        None => None,
+    };
+
+    // Put back the welded vertices
+    let first = chunks.first().map_or(0, |c| c.faces.start);
+    let range = first..first + faces.len();
+    for &(c, v) in &compressed.welded {
+        if range.contains(&(c / 3)) {
+            faces[c / 3 - first][c % 3] = v;
+        }
+    }
+
+    // Restore the input winding
+    for &t in &compressed.flipped {
+        if !range.contains(&t) {
+            continue;
+        }
+        for corners in [&mut faces, &mut mesh.face_texcoords, &mut mesh.face_normals] {
+            if let Some(c) = corners.get_mut(t - first) {
+                c.swap(1, 2);
+            }
+        }
+    }
+
+    // Fan the merged polygons out again from their apex
+    if let Some(groups) = groups {
+        let outlines = groups
+            .iter()
+            .map(|group| polygons::outline(&faces, group))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fan = |corners: &[[usize; 3]]| {
+            outlines
+                .iter()
+                .flat_map(|o| (1..o.len() - 1).map(move |i| [o[0], o[i], o[i + 1]]))
+                .map(|t| t.map(|(f, k)| corners[f][k]))
+                .collect::<Vec<_>>()
+        };
+        for corners in [&mut faces, &mut mesh.face_texcoords, &mut mesh.face_normals] {
+            if !corners.is_empty() {
+                *corners = fan(corners);
+            }
+        }
+        mesh.polygons = outlines.iter().map(Vec::len).collect();
+    }
+    mesh.faces = faces;
+
+    Ok(mesh)
+}
+
+// Decode the connectivity of component `i` alone, with the labels of the
+// whole mesh and without putting back welded vertices or flipped faces
+fn component_faces(
+    compressed: &Compressed,
+    i: usize,
+    options: &DecompressOptions,
+) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let components = &compressed.components;
+    let Some(entry) = components.get(i) else {
+        return Err(EdgeBreakerError::MalformedInput(format!(
+            "component {i} does not exist, the directory has {} entries",
+            components.len()
+        )));
+    };
+    let first_face = components[..i].iter().map(|c| c.faces).sum::<usize>();
+    let first_label = components[..i].iter().map(|c| c.vertices).sum::<usize>();
+    let mismatch = || {
+        EdgeBreakerError::TableMismatch(format!(
+            "component {i} lies outside of the compressed mesh"
+        ))
+    };
+    if entry.vertex_range.end > compressed.vertex_count() {
+        return Err(mismatch());
+    }
+
+    let history = compressed.history.get(first_face..first_face + entry.faces);
+    let next = components.get(i + 1);
+    let table = compressed
+        .table
+        .get(entry.table..next.map_or(compressed.table.len(), |c| c.table));
+    let dup = compressed
+        .dup
+        .get(entry.dup..next.map_or(compressed.dup.len(), |c| c.dup));
+    let (Some(history), Some(table), Some(dup)) = (history, table, dup) else {
+        return Err(mismatch());
+    };
+    let history = history.to_vec();
+    let (lengths, m_table) = side_info(&history, table)?;
+
+    // Duplicates can point back to vertices of earlier components
+    let mut previous = Vec::with_capacity(entry.vertices);
+    let mut v = entry.vertex_range.start;
+    let mut dup = dup.iter().peekable();
+    for label in first_label..first_label + entry.vertices {
+        match dup.next_if(|&&(pos, _)| pos == label) {
+            Some(&(_, idx)) if idx < v => previous.push(Id::from_offset(idx)),
+            Some(_) => return Err(mismatch()),
+            None => {
+                previous.push(Id::from_offset(v));
+                v += 1;
+            }
+        }
+    }
+    if dup.next().is_some() || v != entry.vertex_range.end {
+        return Err(mismatch());
+    }
+
+    let eb = EdgeBreaker {
+        history,
+        previous,
+        lengths,
+        m_table,
+    };
+    decode(&eb, options.decoder)
+}
+
+/// Decode component `i` of the directory without touching the others. Its
+/// vertices come first in compressed order, followed by the vertices of
+/// other components it shares. Every stream starts over at each component,
+/// only quantized positions it shares are decoded with the component that
+/// codes them.
+pub fn decompress_component(
+    compressed: &Compressed,
+    i: usize,
+    options: &DecompressOptions,
+) -> Result<Mesh, EdgeBreakerError> {
+    let faces = component_faces(compressed, i, options)?;
+    let chunks = stream_chunks(compressed);
+    let chunk = &chunks[i];
+    let vertex_count = compressed.vertex_count();
+    let mismatch = || {
+        EdgeBreakerError::TableMismatch(format!(
+            "component {i} lies outside of the compressed mesh"
+        ))
+    };
+
+    let vertices = match &compressed.geometry {
+        Some(geometry) => {
+            let corners = 3 * chunk.faces.start..3 * chunk.faces.end;
+            let welded = compressed
+                .welded
+                .iter()
+                .filter(|(c, _)| corners.contains(c));
+            let mut owners = faces
+                .iter()
+                .flatten()
+                .chain(welded.map(|(_, v)| v))
+                .map(|&v| chunks.partition_point(|c| c.vertices.end <= v))
+                .collect::<Vec<_>>();
+            owners.push(i);
+            owners.sort();
+            owners.dedup();
+            if owners.last() >= Some(&chunks.len()) {
+                return Err(mismatch());
+            }
+
+            // Predictions follow the decoding order of the whole mesh
+            let mut decoded = Vec::new();
+            let mut starts = Vec::with_capacity(owners.len());
+            for &k in &owners {
+                starts.push(decoded.len());
+                match k == i {
+                    true => decoded.extend_from_slice(&faces),
+                    false => decoded.extend(component_faces(compressed, k, options)?),
+                }
+            }
+            let coded = owners
+                .iter()
+                .map(|&k| (chunks[k].vertices.clone(), chunks[k].geometry));
+            let predictions = geometry::predictions(&decoded, vertex_count, &starts);
+            geometry::decode(geometry, &predictions, &coded.collect::<Vec<_>>())?
+        }
+        None => compressed.vertices.clone(),
+    };
+    let mut mesh = decode_streams(compressed, faces, vertices, &chunks[i..=i])?;
+    if mesh.faces.iter().flatten().any(|&v| v >= vertex_count) {
+        return Err(mismatch());
+    }
+
+    // Number the vertices of the component from zero
+    let range = compressed.components[i].vertex_range.clone();
+    let mut used = range.clone().collect::<Vec<_>>();
+    let mut local = HashMap::new();
+    mesh.faces = mesh
+        .faces
+        .iter()
+        .map(|f| {
+            f.map(|v| match range.contains(&v) {
+                true => v - range.start,
+                false => *local.entry(v).or_insert_with(|| {
+                    used.push(v);
+                    used.len() - 1
+                }),
+            })
+        })
+        .collect();
+    mesh.vertices = used.iter().map(|&v| mesh.vertices[v]).collect();
+    for p in mesh.properties.iter_mut() {
+        p.values = used.iter().map(|&v| p.values[v]).collect();
+    }
+    Ok(mesh)
+}
+
+/// Pass the faces of `compressed` to `emit` in decoding order. The forward
+/// decoder emits every face as soon as it is generated, the others only
+/// after decoding all of them. Faces are not merged into polygons.
+pub fn decompress_faces<F>(
+    compressed: &Compressed,
+    options: &DecompressOptions,
+    mut emit: F,
+) -> Result<(), EdgeBreakerError>
+where
+    F: FnMut([usize; 3]) -> Result<(), EdgeBreakerError>,
+{
+    let eb = unpack(compressed)?;
+    let mut flipped = compressed.flipped.iter().peekable();
+    let mut welded = compressed.welded.iter().peekable();
+    let mut t = 0;
+    let mut emit = |mut f: [usize; 3]| {
+        while let Some(&(c, v)) = welded.next_if(|&&(c, _)| c < 3 * t + 3) {
+            f[c % 3] = v;
+        }
+        if flipped.next_if_eq(&&t).is_some() {
+            f.swap(1, 2);
+        }
+        t += 1;
+        emit(f)
+    };
+    let faces = match options.decoder {
+        DecoderKind::Forward => return decompression::decompress_each(&eb, emit),
+        decoder => decode(&eb, decoder)?,
+    };
+    faces.into_iter().try_for_each(&mut emit)
+}
+
+pub fn compress_obj(obj: &mut Obj, options: &CompressOptions) -> Result<(), EdgeBreakerError> {
+    let mesh = Mesh::from(std::mem::take(obj));
+    *obj = Obj::from(compress_mesh(&mesh, options)?);
+    Ok(())
+}
+
+pub fn decompress_obj(obj: &mut Obj) -> Result<(), EdgeBreakerError> {
+    let compressed = Compressed::from(std::mem::take(obj));
+    *obj = Obj::from(decompress_mesh(&compressed, &DecompressOptions::default())?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad() -> Mesh {
+        Mesh {
+            vertices: vec![[0.0; 3]; 4],
+            faces: vec![[0, 1, 2], [0, 2, 3]],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn corrupt_input_is_an_error() {
+        let mut compressed = compress_mesh(&quad(), &CompressOptions::default()).unwrap();
+        compressed.history.pop();
+        assert!(matches!(
+            decompress_mesh(&compressed, &DecompressOptions::default()),
+            Err(EdgeBreakerError::CorruptHistory(_))
+        ));
+
+        let mut compressed = compress_mesh(&quad(), &CompressOptions::default()).unwrap();
+        compressed.table.push(Table::Hole(3));
+        assert!(matches!(
+            decompress_mesh(&compressed, &DecompressOptions::default()),
+            Err(EdgeBreakerError::TableMismatch(_))
+        ));
+
+        let mut mesh = quad();
+        mesh.faces.push([0, 3, 4]);
+        assert!(matches!(
+            compress_mesh(&mesh, &CompressOptions::default()),
+            Err(EdgeBreakerError::MalformedInput(_))
+        ));
+    }
+    #[test]
+    fn conditioned_code_falls_back_to_prefix() {
+        // The repeated face is closed by E right after its C
+        let mesh = Mesh {
+            vertices: points(4),
+            faces: vec![[1, 3, 0], [2, 0, 1], [2, 0, 1]],
+            ..Default::default()
+        };
+        let options = CompressOptions {
+            encoding: HistoryEncoding::Conditioned,
+            ..Default::default()
+        };
+        let compressed = compress_mesh(&mesh, &options).unwrap();
+        assert_eq!(compressed.encoding, HistoryEncoding::Prefix);
+        let decoded = decompress_mesh(&compressed, &DecompressOptions::default()).unwrap();
+        assert_eq!(shape(&decoded), shape(&mesh));
+    }
+    #[test]
+    fn degenerate_faces_are_rejected() {
+        for face in [[0, 1, 1], [2, 2, 2], [3, 0, 3]] {
+            let mut mesh = quad();
+            mesh.faces.push(face);
+            assert!(matches!(
+                compress_mesh(&mesh, &CompressOptions::default()),
+                Err(EdgeBreakerError::MalformedInput(_))
+            ));
+        }
+    }
+    #[test]
+    fn streamed_faces_match_decompressed() {
+        let compressed = compress_mesh(&quad(), &CompressOptions::default()).unwrap();
+        for decoder in [
+            DecoderKind::Forward,
+            DecoderKind::SpiraleReversi,
+            DecoderKind::WrapZip,
+        ] {
+            let options = DecompressOptions {
+                decoder,
+                ..Default::default()
+            };
+            let mut faces = Vec::new();
+            decompress_faces(&compressed, &options, |f| {
+                faces.push(f);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(faces, decompress_mesh(&compressed, &options).unwrap().faces);
+        }
+    }
+    #[test]
+    fn isolated_vertices_are_kept() {
+        let mut mesh = quad();
+        mesh.vertices.insert(1, [5.0; 3]);
+        mesh.vertices.push([7.0; 3]);
+        mesh.faces = vec![[0, 2, 3], [0, 3, 4]];
+
+        let encoded = encode_mesh(&mesh, &CompressOptions::default()).unwrap();
+        assert_eq!(encoded.isolated, 2);
+        assert_eq!(encoded.order[4..], [1, 5]);
+        let decoded = decompress_mesh(&encoded.compressed, &DecompressOptions::default()).unwrap();
+        assert_eq!(decoded.vertices.len(), 6);
+        assert_eq!(decoded.vertices[4..], [[5.0; 3], [7.0; 3]]);
+    }
+
+    #[test]
+    fn duplicate_vertices_are_welded() {
+        // Quad whose second triangle has its own copies of the diagonal
+        let mut mesh = quad();
+        mesh.vertices = points(4);
+        mesh.vertices.push(mesh.vertices[0]);
+        mesh.vertices.push(mesh.vertices[2].map(|c| c + 1e-4));
+        mesh.faces[1] = [4, 5, 3];
+        let ends = |c: &Compressed| c.history.iter().filter(|&&op| op == Op::E).count();
+
+        let compressed = compress_mesh(&mesh, &CompressOptions::default()).unwrap();
+        assert_eq!(ends(&compressed), 2);
+
+        let mut options = CompressOptions {
+            weld: Some(1e-3),
+            ..Default::default()
+        };
+        let encoded = encode_mesh(&mesh, &options).unwrap();
+        assert_eq!(encoded.welds, [0, 1, 2, 3, 0, 2]);
+        assert_eq!(ends(&encoded.compressed), 1);
+        assert_eq!(encoded.compressed.vertex_count(), 4);
+
+        options.restore_welded = true;
+        let compressed = compress_mesh(&mesh, &options).unwrap();
+        assert_eq!(ends(&compressed), 1);
+        assert_eq!(compressed.welded.len(), 2);
+        let decoded = decompress_mesh(&compressed, &DecompressOptions::default()).unwrap();
+        assert_eq!(decoded.vertices.len(), 6);
+        assert_eq!(shape(&decoded), shape(&mesh));
+
+        let mut faces = Vec::new();
+        decompress_faces(&compressed, &DecompressOptions::default(), |f| {
+            faces.push(f);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(faces, decoded.faces);
+    }
+
+    // Faces by their corner positions, starting at the smallest corner
+    fn shape(mesh: &Mesh) -> Vec<[[u32; 3]; 3]> {
+        let mut faces = mesh
+            .faces
+            .iter()
+            .map(|f| {
+                let mut t = f.map(|v| mesh.vertices[v].map(f32::to_bits));
+                let k = (0..3).min_by_key(|&k| t[k]).unwrap();
+                t.rotate_left(k);
+                t
+            })
+            .collect::<Vec<_>>();
+        faces.sort();
+        faces
+    }
+
+    fn assert_round_trip(mesh: &Mesh) {
+        let compressed = compress_mesh(mesh, &CompressOptions::default()).unwrap();
+        for decoder in [
+            DecoderKind::Forward,
+            DecoderKind::SpiraleReversi,
+            DecoderKind::WrapZip,
+        ] {
+            let decoded = decompress_mesh(
+                &compressed,
+                &DecompressOptions {
+                    decoder,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            assert_eq!(decoded.vertices.len(), mesh.vertices.len(), "{decoder:?}");
+            assert_eq!(shape(&decoded), shape(mesh), "{decoder:?}");
+        }
+    }
+
+    fn points(n: usize) -> Vec<[f32; 3]> {
+        (0..n).map(|i| [i as f32, (i * i) as f32, 1.0]).collect()
+    }
+
+    #[test]
+    fn mesh_without_faces_round_trips() {
+        let compressed = compress_mesh(&Mesh::default(), &CompressOptions::default()).unwrap();
+        assert!(compressed.history.is_empty());
+        assert_round_trip(&Mesh::default());
+        assert_round_trip(&Mesh {
+            vertices: points(3),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn single_triangle_round_trips() {
+        assert_round_trip(&Mesh {
+            vertices: points(3),
+            faces: vec![[0, 1, 2]],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn closed_tetrahedron_round_trips() {
+        assert_round_trip(&Mesh {
+            vertices: points(4),
+            faces: vec![[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn isolated_triangles_round_trip() {
+        assert_round_trip(&Mesh {
+            vertices: points(9),
+            faces: vec![[0, 1, 2], [5, 4, 3], [6, 8, 7]],
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn components_with_holes_round_trip() {
+        // Two 3x3 grids without their middle square
+        let mut mesh = Mesh {
+            vertices: points(32),
+            ..Default::default()
+        };
+        for base in [0, 16] {
+            for j in 0..3 {
+                for i in (0..3).filter(|&i| (i, j) != (1, 1)) {
+                    let a = base + j * 4 + i;
+                    mesh.faces.extend([[a, a + 1, a + 5], [a, a + 5, a + 4]]);
+                }
+            }
+        }
+        for threads in [0, 2] {
+            let options = CompressOptions {
+                threads,
+                ..Default::default()
+            };
+            let compressed = compress_mesh(&mesh, &options).unwrap();
+            assert!(compressed.history.contains(&Op::H));
+            let decoded = decompress_mesh(&compressed, &DecompressOptions::default()).unwrap();
+            assert_eq!(shape(&decoded), shape(&mesh));
+        }
+    }
+
+    // Grid with a hole and a flipped face, two tetrahedra sharing a vertex
+    // and a triangle
+    fn parts() -> Mesh {
+        let tet = [[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]];
+        let mut mesh = Mesh {
+            vertices: points(26),
+            faces: tet.map(|f| f.map(|v| [16, 17, 18, 19][v])).to_vec(),
+            ..Default::default()
+        };
+        for j in 0..3 {
+            for i in (0..3).filter(|&i| (i, j) != (1, 1)) {
+                let a = j * 4 + i;
+                mesh.faces.extend([[a, a + 1, a + 5], [a, a + 5, a + 4]]);
+            }
+        }
+        mesh.faces[4] = [0, 5, 1];
+        mesh.faces
+            .extend(tet.map(|f| f.map(|v| [16, 20, 21, 22][v])));
+        mesh.faces.push([23, 24, 25]);
+        mesh
+    }
+
+    #[test]
+    fn components_compress_in_parallel() {
+        let mesh = parts();
+        let options = CompressOptions {
+            threads: 3,
+            ..Default::default()
+        };
+        let compressed = compress_mesh(&mesh, &options).unwrap();
+        let faces = compressed.components.iter().map(|c| c.faces);
+        assert_eq!(faces.collect::<Vec<_>>(), [4, 16, 4, 1]);
+
+        let sequential = decompress_mesh(&compressed, &DecompressOptions::default()).unwrap();
+        let parallel = DecompressOptions {
+            threads: 4,
+            ..Default::default()
+        };
+        let decoded = decompress_mesh(&compressed, &parallel).unwrap();
+        assert_eq!(decoded.faces, sequential.faces);
+        assert_eq!(shape(&decoded), shape(&mesh));
+
+        let mut corrupt = compressed.clone();
+        corrupt.components[0].faces += 1;
+        assert!(matches!(
+            decompress_mesh(&corrupt, &parallel),
+            Err(EdgeBreakerError::TableMismatch(_))
+        ));
+    }
+
+    #[test]
+    fn quantized_parts_round_trip() {
+        // Two fins on an inner edge of the grid make it crowded. The points
+        // are whole numbers, quantizing to 16 bits keeps them apart.
+        let mut mesh = parts();
+        mesh.vertices.extend(points(28).split_off(26));
+        mesh.faces.extend([[5, 6, 26], [6, 5, 27]]);
+        let rounded = |mut mesh: Mesh| {
+            for v in mesh.vertices.iter_mut() {
+                *v = v.map(f32::round);
+            }
+            shape(&mesh)
+        };
+        for threads in [0, 2] {
+            let options = CompressOptions {
+                threads,
+                quantization: Some(16),
+                ..Default::default()
+            };
+            let compressed = compress_mesh(&mesh, &options).unwrap();
+            let decoded = decompress_mesh(&compressed, &DecompressOptions::default()).unwrap();
+            assert_eq!(rounded(decoded), shape(&mesh));
+
+            let mut faces = (0..compressed.components.len())
+                .map(|i| decompress_component(&compressed, i, &DecompressOptions::default()))
+                .flat_map(|part| rounded(part.unwrap()))
+                .collect::<Vec<_>>();
+            faces.sort();
+            if threads > 0 {
+                assert_eq!(faces, shape(&mesh));
+            }
+        }
+    }
+
+    #[test]
+    fn components_decode_on_their_own() {
+        let mesh = parts();
+        let mut options = CompressOptions {
+            threads: 2,
+            ..Default::default()
+        };
+        let compressed = compress_mesh(&mesh, &options).unwrap();
+        assert!(
+            compressed
+                .table
+                .iter()
+                .any(|t| matches!(t, Table::Hole(..)))
+        );
+        assert_eq!(compressed.flipped.len(), 1);
+
+        // The second tetrahedron shares its first vertex with the first one
+        let decode = |compressed: &Compressed, i| {
+            decompress_component(compressed, i, &DecompressOptions::default()).unwrap()
+        };
+        let parts = (0..4).map(|i| decode(&compressed, i)).collect::<Vec<_>>();
+        let counts = parts.iter().map(|m| (m.faces.len(), m.vertices.len()));
+        assert_eq!(
+            counts.collect::<Vec<_>>(),
+            [(4, 4), (16, 16), (4, 4), (1, 3)]
+        );
+        assert_eq!(compressed.components[2].vertex_range.len(), 3);
+        let mut faces = parts.iter().flat_map(shape).collect::<Vec<_>>();
+        faces.sort();
+        assert_eq!(faces, shape(&mesh));
+
+        // The shared vertex is quantized with the first tetrahedron
+        options.quantization = Some(16);
+        let quantized = compress_mesh(&mesh, &options).unwrap();
+        let whole = decompress_mesh(&quantized, &DecompressOptions::default()).unwrap();
+        let mut faces = (0..4)
+            .flat_map(|i| shape(&decode(&quantized, i)))
+            .collect::<Vec<_>>();
+        faces.sort();
+        assert_eq!(faces, shape(&whole));
+
+        assert!(decompress_component(&compressed, 4, &DecompressOptions::default()).is_err());
+    }
+
+    #[test]
+    fn components_read_only_their_streams() {
+        let mut mesh = parts();
+        mesh.texcoords = mesh.vertices.iter().map(|v| [v[0], v[1]]).collect();
+        mesh.normals = mesh.vertices.clone();
+        mesh.face_texcoords = mesh.faces.clone();
+        mesh.face_normals = mesh.faces.clone();
+        mesh.polygons = [vec![3; 4], vec![4; 8], vec![3; 5]].concat();
+        let options = CompressOptions {
+            threads: 2,
+            quantization: Some(16),
+            ..Default::default()
+        };
+        let compressed = compress_mesh(&mesh, &options).unwrap();
+        let default = DecompressOptions::default();
+        assert!(compressed.polygons.is_some());
+
+        // Invert the bytes every other component starts over at
+        fn scramble(data: &mut [u8], offsets: &[usize], keep: &[usize]) {
+            for k in (0..offsets.len()).filter(|k| !keep.contains(k)) {
+                let end = offsets.get(k + 1).copied().unwrap_or(data.len());
+                data[offsets[k]..end].iter_mut().for_each(|b| *b = !*b);
+            }
+        }
+        let components = &compressed.components;
+        let offsets =
+            |f: fn(&ComponentEntry) -> usize| components.iter().map(f).collect::<Vec<_>>();
+
+        // The second tetrahedron also reads the position it shares
+        for keep in [&[1][..], &[0, 2], &[3]] {
+            let mut scrambled = compressed.clone();
+            let geometry = scrambled.geometry.as_mut().unwrap();
+            scramble(&mut geometry.data, &offsets(|c| c.geometry), keep);
+            let texcoords = scrambled.texcoords.as_mut().unwrap();
+            scramble(&mut texcoords.corners, &offsets(|c| c.texcoords.0), keep);
+            let normals = scrambled.normals.as_mut().unwrap();
+            scramble(&mut normals.corners, &offsets(|c| c.normals.0), keep);
+            let polygons = scrambled.polygons.as_mut().unwrap();
+            scramble(polygons, &offsets(|c| c.polygons), keep);
+
+            let i = *keep.last().unwrap();
+            assert_eq!(
+                decompress_component(&scrambled, i, &default).unwrap(),
+                decompress_component(&compressed, i, &default).unwrap()
+            );
+            let whole = decompress_mesh(&compressed, &default).unwrap();
+            assert!(decompress_mesh(&scrambled, &default).ok() != Some(whole));
+        }
+    }
 }
@@ -0,0 +1,101 @@
+// ,---------------------------------------------------------------------------
+// | Orientation: consistent winding per component
+// '---------------------------------------------------------------------------
+
+use super::manifold::for_each_edge;
+
+/// Find the faces to flip so that every edge shared by exactly two faces is
+/// used in opposite directions. Each component keeps the winding of most of
+/// its faces. Faces of non-orientable components can still disagree, those
+/// edges are cut apart like non-manifold ones.
+pub fn orient(faces: &[[usize; 3]]) -> Vec<bool> {
+    // Other half-edge of every edge with exactly two
+    let vertex_count = faces.iter().flatten().max().map_or(0, |&v| v + 1);
+    let mut mate = vec![None; faces.len() * 3];
+    for_each_edge(faces, vertex_count, |hs| {
+        if let [x, y] = *hs {
+            mate[x] = Some(y);
+            mate[y] = Some(x);
+        }
+    });
+
+    let mut flipped = vec![false; faces.len()];
+    let mut seen = vec![false; faces.len()];
+    for seed in 0..faces.len() {
+        if seen[seed] {
+            continue;
+        }
+        seen[seed] = true;
+        let mut component = vec![seed];
+        let mut i = 0;
+        while i < component.len() {
+            let f = component[i];
+            i += 1;
+            for k in 0..3 {
+                let Some(g) = mate[3 * f + k] else {
+                    continue;
+                };
+                let n = g / 3;
+                if n == f || seen[n] {
+                    continue;
+                }
+                // Starts where this half-edge starts, so it needs the other winding
+                let same = faces[n][g % 3] == faces[f][k];
+                flipped[n] = same != flipped[f];
+                seen[n] = true;
+                component.push(n);
+            }
+        }
+
+        let count = component.iter().filter(|&&f| flipped[f]).count();
+        if 2 * count > component.len() {
+            for f in component {
+                flipped[f] = !flipped[f];
+            }
+        }
+    }
+
+    flipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CompressOptions, Mesh, compress_with, verify};
+
+    #[test]
+    fn flipped_faces_are_restored_or_fixed() {
+        // 3x3 grid with two faces wound the other way
+        let mut mesh = Mesh::default();
+        for i in 0..16 {
+            mesh.vertices.push([(i % 4) as f32, (i / 4) as f32, 0.0]);
+        }
+        for j in 0..3 {
+            for i in 0..3 {
+                let a = j * 4 + i;
+                mesh.faces.push([a, a + 1, a + 5]);
+                mesh.faces.push([a, a + 5, a + 4]);
+            }
+        }
+        mesh.faces[4] = [2, 7, 3];
+        mesh.faces[11] = [6, 10, 11];
+
+        let flipped = orient(&mesh.faces);
+        assert_eq!(flipped.iter().filter(|&&f| f).count(), 2);
+        assert!(flipped[4] && flipped[11]);
+
+        let compressed = compress_with(&mesh, &CompressOptions::default()).unwrap();
+        assert!(compressed.dup.is_empty());
+        assert_eq!(compressed.flipped.len(), 2);
+        let report = verify(&mesh, &CompressOptions::default()).unwrap();
+        assert!(report.is_ok(), "{report}");
+
+        let options = CompressOptions {
+            fix_orientation: true,
+            ..Default::default()
+        };
+        let compressed = compress_with(&mesh, &options).unwrap();
+        assert!(compressed.flipped.is_empty());
+        assert_eq!(verify(&mesh, &options).unwrap().flipped.len(), 2);
+    }
+}
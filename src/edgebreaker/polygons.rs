@@ -0,0 +1,229 @@
+// ,---------------------------------------------------------------------------
+// | Polygons: merging decoded triangles back into the input polygons
+// '---------------------------------------------------------------------------
+
+use std::{collections::HashMap, ops::Range};
+
+use super::{
+    arithmetic::{Decoder, Encoder, Model},
+    chunks,
+};
+use crate::EdgeBreakerError;
+
+// Every edge between a face and one decoded before it gets a bit that tells
+// whether the edge is a diagonal, i.e. whether both faces belong to the same
+// polygon. The bit is coded in the context of whether either face already
+// has a diagonal, which makes the other edges of quads almost free.
+struct Diagonals {
+    models: [Model<2>; 4],
+    edges: HashMap<(usize, usize), Vec<usize>>,
+    found: Vec<bool>,
+}
+
+impl Diagonals {
+    fn new(face_count: usize) -> Self {
+        Self {
+            models: Default::default(),
+            edges: HashMap::new(),
+            found: vec![false; face_count],
+        }
+    }
+
+    // Visit the edges of face `t` that it shares with earlier faces
+    fn visit<F>(&mut self, t: usize, f: [usize; 3], mut code: F) -> Result<(), EdgeBreakerError>
+    where
+        F: FnMut(&mut Model<2>, usize) -> Result<bool, EdgeBreakerError>,
+    {
+        for k in 0..3 {
+            let (a, b) = (f[k], f[(k + 1) % 3]);
+            let earlier = self.edges.entry((a.min(b), a.max(b))).or_default();
+            for &u in earlier.iter() {
+                let context = self.found[t] as usize + 2 * self.found[u] as usize;
+                if code(&mut self.models[context], u)? {
+                    self.found[t] = true;
+                    self.found[u] = true;
+                }
+            }
+            earlier.push(t);
+        }
+        Ok(())
+    }
+}
+
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+/// Encode which of the decoded `faces` belong to the same polygon, given the
+/// polygon of every face. Coding starts over at every face in `starts`,
+/// which polygons must not cross. Returns the byte offset of each start.
+pub fn encode(faces: &[[usize; 3]], polygon: &[usize], starts: &[usize]) -> (Vec<u8>, Vec<usize>) {
+    let mut bytes = Vec::new();
+    let mut offsets = Vec::with_capacity(starts.len());
+    for range in chunks(starts, faces.len()) {
+        let mut encoder = Encoder::new();
+        let mut diagonals = Diagonals::new(range.len());
+        for (t, &f) in faces[range.clone()].iter().enumerate() {
+            let _ = diagonals.visit(t, f, |model, u| {
+                let same = polygon[range.start + t] == polygon[range.start + u];
+                encoder.encode(model, same as usize);
+                Ok(same)
+            });
+        }
+        offsets.push(bytes.len());
+        bytes.extend(encoder.finish());
+    }
+    (bytes, offsets)
+}
+
+/// Group the decoded `faces` into polygons, ordered by their first face.
+/// Each of `chunks` gives a run of faces and the byte offset their coding
+/// starts at.
+pub fn decode(
+    data: &[u8],
+    faces: &[[usize; 3]],
+    chunks: &[(Range<usize>, usize)],
+) -> Result<Vec<Vec<usize>>, EdgeBreakerError> {
+    let mut parent = (0..faces.len()).collect::<Vec<_>>();
+    for (range, offset) in chunks {
+        let (Some(chunk), Some(data)) = (faces.get(range.clone()), data.get(*offset..)) else {
+            return Err(EdgeBreakerError::MalformedInput(format!(
+                "diagonals of faces {range:?} at byte {offset} lie outside of the stream"
+            )));
+        };
+        let mut decoder = Decoder::new(data);
+        let mut diagonals = Diagonals::new(chunk.len());
+        for (t, &f) in chunk.iter().enumerate() {
+            diagonals.visit(t, f, |model, u| {
+                let same = decoder.decode(model)? == 1;
+                if same {
+                    let (x, y) = (
+                        find(&mut parent, range.start + t),
+                        find(&mut parent, range.start + u),
+                    );
+                    parent[x.max(y)] = x.min(y);
+                }
+                Ok(same)
+            })?;
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut index = vec![usize::MAX; faces.len()];
+    for t in 0..faces.len() {
+        let root = find(&mut parent, t);
+        if root == t {
+            index[t] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[index[root]].push(t);
+    }
+
+    Ok(groups)
+}
+
+/// Walk the edges of the polygon made of `group` that are not shared by two
+/// of its faces. Returns its corners `(face, k)` in order, starting at the
+/// corner shared by all faces, so that fanning from it gives back the faces.
+pub fn outline(
+    faces: &[[usize; 3]],
+    group: &[usize],
+) -> Result<Vec<(usize, usize)>, EdgeBreakerError> {
+    let corrupt = || {
+        EdgeBreakerError::CorruptHistory(format!(
+            "faces {group:?} do not form a polygon with a single outline"
+        ))
+    };
+    let start = |(f, k): (usize, usize)| faces[f][k];
+    let end = |(f, k): (usize, usize)| faces[f][(k + 1) % 3];
+
+    let corners = group.iter().flat_map(|&f| (0..3).map(move |k| (f, k)));
+    let outer = corners
+        .clone()
+        .filter(|&h| {
+            !corners
+                .clone()
+                .any(|g| start(g) == end(h) && end(g) == start(h))
+        })
+        .collect::<Vec<_>>();
+
+    // The fan apex is the only vertex of every face, or one of two for quads
+    let apex = outer
+        .iter()
+        .find(|&&h| group.iter().all(|&f| faces[f].contains(&start(h))))
+        .ok_or_else(corrupt)?;
+
+    let mut polygon = vec![*apex];
+    loop {
+        let last = *polygon.last().unwrap();
+        let next = outer
+            .iter()
+            .find(|&&h| start(h) == end(last))
+            .ok_or_else(corrupt)?;
+        if next == apex {
+            break;
+        }
+        if polygon.len() > outer.len() {
+            return Err(corrupt());
+        }
+        polygon.push(*next);
+    }
+    if polygon.len() != group.len() + 2 {
+        return Err(corrupt());
+    }
+    Ok(polygon)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CompressOptions, Mesh, compress, compress_with, decompress};
+
+    #[test]
+    fn polygons_roundtrip() {
+        // Quad, pentagon and triangle around vertex 0, all fan triangulated
+        let mut mesh = Mesh {
+            vertices: (0..8).map(|i| [i as f32, (i * i) as f32, 0.0]).collect(),
+            faces: vec![[0, 1, 2], [0, 2, 3]],
+            polygons: vec![4, 5, 3],
+            ..Default::default()
+        };
+        mesh.faces.extend([[3, 4, 5], [3, 5, 6], [3, 6, 0]]);
+        mesh.faces.push([0, 6, 7]);
+
+        let decoded = decompress(&compress(&mesh).unwrap()).unwrap();
+        let outlines = |m: &Mesh| {
+            let mut faces = m.faces.iter();
+            let mut outlines = m
+                .polygons
+                .iter()
+                .map(|&n| {
+                    let fan = faces.by_ref().take(n - 2).collect::<Vec<_>>();
+                    let mut corners = [fan[0][0], fan[0][1]]
+                        .into_iter()
+                        .chain(fan.iter().map(|f| f[2]))
+                        .map(|v| m.vertices[v][0] as usize)
+                        .collect::<Vec<_>>();
+                    let r = (0..n).min_by_key(|&r| corners[r]).unwrap();
+                    corners.rotate_left(r);
+                    corners
+                })
+                .collect::<Vec<_>>();
+            outlines.sort();
+            outlines
+        };
+        assert_eq!(decoded.polygons.len(), 3);
+        assert_eq!(outlines(&decoded), outlines(&mesh));
+
+        let options = CompressOptions {
+            triangulate: true,
+            ..Default::default()
+        };
+        let decoded = decompress(&compress_with(&mesh, &options).unwrap()).unwrap();
+        assert!(decoded.polygons.is_empty());
+        assert_eq!(decoded.faces.len(), 6);
+    }
+}
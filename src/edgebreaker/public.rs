@@ -1,9 +1,392 @@
+use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
+use bitvec::{bitvec, order::Msb0, vec::BitVec, view::BitView};
+
+use std::ops::Range;
+
+use super::arithmetic::{Decoder, Encoder, Model};
+use crate::{EdgeBreakerError, VertexProperty};
+
 // ,---------------------------------------------------------------------------
-// | Op: history commands
+// | Compressed: output of the encoder
 // '---------------------------------------------------------------------------
 
-use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
-use bitvec::{bitvec, order::Msb0, view::BitView};
+/// Side information for the `H` and `M` operations, in history order: the
+/// length of a hole, and the stack position, offset and length of a merge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Hole(usize),
+    Merge(usize, usize, usize),
+}
+
+// Every value is coded as the bit length of the value plus one, modelled per
+// field, followed by its remaining bits. This is an Elias-gamma code whose
+// length prefix adapts to the mesh.
+const TABLE_CLASSES: usize = 64;
+
+impl Table {
+    /// Encode `table` into bytes, which are empty if it is. Decoding takes
+    /// the kind of every entry from the history.
+    pub fn encode(table: &[Table]) -> Vec<u8> {
+        if table.is_empty() {
+            return Vec::new();
+        }
+        let mut models: [Model<TABLE_CLASSES>; 4] = Default::default();
+        let mut encoder = Encoder::new();
+        let mut put = |field: usize, value: usize| {
+            let x = value as u64 + 1;
+            let class = u64::BITS - x.leading_zeros() - 1;
+            encoder.encode(&mut models[field], class as usize);
+            encoder.encode_bits(x, class);
+        };
+        for entry in table {
+            match *entry {
+                Table::Hole(l) => put(0, l),
+                Table::Merge(p, o, l) => {
+                    put(1, p);
+                    put(2, o);
+                    put(3, l);
+                }
+            }
+        }
+        encoder.finish()
+    }
+
+    /// Decode the entries of the `H` and `M` operations of `history`.
+    pub fn decode(bytes: &[u8], history: &[Op]) -> Result<Vec<Table>, EdgeBreakerError> {
+        let mut models: [Model<TABLE_CLASSES>; 4] = Default::default();
+        let mut decoder = Decoder::new(bytes);
+        let mut get = |field: usize| -> Result<usize, EdgeBreakerError> {
+            let class = decoder.decode(&mut models[field])? as u32;
+            Ok((((1 << class) | decoder.decode_bits(class)?) - 1) as usize)
+        };
+        let mut table = Vec::new();
+        for op in history {
+            match op {
+                Op::H => table.push(Table::Hole(get(0)?)),
+                Op::M => table.push(Table::Merge(get(1)?, get(2)?, get(3)?)),
+                _ => {}
+            }
+        }
+        Ok(table)
+    }
+}
+
+/// Where one connected component is found in the compressed mesh. Entries
+/// follow the order of the components in the history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentEntry {
+    /// Number of faces, which is also the number of operations
+    pub faces: usize,
+    /// Number of vertex labels, including the duplicate ones
+    pub vertices: usize,
+    /// Bit offset of the first operation in the coded history
+    pub history_bit: usize,
+    /// Index of the first table entry
+    pub table: usize,
+    /// Index of the first duplicate entry
+    pub dup: usize,
+    /// Vertices first used by this component. Its duplicate entries can
+    /// also reference vertices of earlier components.
+    pub vertex_range: Range<usize>,
+    /// Byte offset of the first quantized position of the component, which
+    /// also codes the vertices up to the next component
+    pub geometry: usize,
+    /// Byte offset of the texture coordinate corners of the component and
+    /// number of texture coordinates used before it
+    pub texcoords: (usize, usize),
+    /// Same as `texcoords` for the normals
+    pub normals: (usize, usize),
+    /// Byte offset of the diagonal bits of the component
+    pub polygons: usize,
+}
+
+/// Compressed connectivity together with the vertices in traversal order.
+#[derive(Debug, Clone, Default)]
+pub struct Compressed {
+    pub vertices: Vec<[f32; 3]>,
+    pub history: Vec<Op>,
+    pub table: Vec<Table>,
+    pub dup: Vec<(usize, usize)>,
+    pub encoding: HistoryEncoding,
+    /// Replaces `vertices` when the geometry was quantized
+    pub geometry: Option<QuantizedGeometry>,
+    pub texcoords: Option<Attribute<2>>,
+    pub normals: Option<Attribute<3>>,
+    /// Extra per-vertex data in the same order as the vertices
+    pub properties: Vec<VertexProperty>,
+    /// Decoded faces, in ascending order, whose winding was made consistent
+    /// with their neighbours and is reversed again after decoding
+    pub flipped: Vec<usize>,
+    /// Decoded corners, as (3 * face + corner, vertex) in ascending order,
+    /// whose input vertex was welded into another one and is put back after
+    /// decoding
+    pub welded: Vec<(usize, usize)>,
+    /// Arithmetic coded diagonal bits that merge the decoded faces back into
+    /// the input polygons
+    pub polygons: Option<Vec<u8>>,
+    /// Directory of the connected components, which lets them be decoded in
+    /// parallel. Empty unless they were compressed separately.
+    pub components: Vec<ComponentEntry>,
+}
+
+impl Compressed {
+    pub fn vertex_count(&self) -> usize {
+        match &self.geometry {
+            Some(geometry) => geometry.count,
+            None => self.vertices.len(),
+        }
+    }
+}
+
+/// Vertex positions quantized to `bits` per coordinate inside the bounding
+/// box `min`..`max`, stored as entropy coded prediction residuals.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedGeometry {
+    pub bits: u8,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub count: usize,
+    pub data: Vec<u8>,
+}
+
+/// Per-corner attribute such as texture coordinates or normals. Values are
+/// stored in order of first use and `corners` holds the entropy coded index
+/// of every corner in decoding order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute<const N: usize> {
+    pub values: Vec<[f32; N]>,
+    pub corners: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompressOptions {
+    pub encoding: HistoryEncoding,
+    /// Quantize vertex positions to this many bits per coordinate
+    pub quantization: Option<u8>,
+    /// Keep the corrected winding of inconsistently oriented faces instead
+    /// of recording them to restore the input
+    pub fix_orientation: bool,
+    /// Drop the polygons of the input and keep only their triangles
+    pub triangulate: bool,
+    /// Compress every connected component on its own, on this many threads,
+    /// and store a component directory. 0 compresses the whole mesh at once.
+    pub threads: usize,
+    /// Weld vertices closer than this to each other before compressing, so
+    /// that the faces around duplicate positions connect. Zero welds only
+    /// equal positions.
+    pub weld: Option<f32>,
+    /// Record the corners that used a welded vertex, so that decompressing
+    /// restores the input vertices and faces
+    pub restore_welded: bool,
+}
+
+/// Algorithm used to rebuild the faces from the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecoderKind {
+    /// Precomputes the offset of every S, then decodes front to back
+    #[default]
+    Forward,
+    /// Decodes back to front without offsets
+    SpiraleReversi,
+    /// Builds the triangles with placeholder vertices, then zips them
+    /// together. Needs offsets only for components with M.
+    WrapZip,
+}
+
+impl DecoderKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DecoderKind::Forward => "forward",
+            DecoderKind::SpiraleReversi => "spirale-reversi",
+            DecoderKind::WrapZip => "wrap-zip",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "forward" => Some(DecoderKind::Forward),
+            "spirale-reversi" => Some(DecoderKind::SpiraleReversi),
+            "wrap-zip" => Some(DecoderKind::WrapZip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecompressOptions {
+    pub decoder: DecoderKind,
+    /// Decode the components on this many threads if the compressed mesh
+    /// has a component directory
+    pub threads: usize,
+}
+
+// ,---------------------------------------------------------------------------
+// | HistoryEncoding: bit layout of the history
+// '---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistoryEncoding {
+    /// Fixed prefix code: C=0, S/H/M=100, R=101, L=110, E=111. A leading
+    /// bit tells whether the history has H or M. Only then is the code
+    /// shared by S, H and M followed by an escape: S=0, H=10, M=11.
+    #[default]
+    Prefix,
+    /// Adaptive arithmetic coding conditioned on the two previous operations
+    Arithmetic,
+    /// Prefix code conditioned on the previous operation. L and E never
+    /// follow C, so after a C the code is C=0, S/H/M=10, R=11. This bounds
+    /// the history of a simple mesh to 3.67 bits per vertex. H and M are
+    /// escaped as in the prefix code. Histories where L or E does follow C
+    /// are compressed with [`HistoryEncoding::Prefix`] instead.
+    Conditioned,
+}
+
+impl HistoryEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HistoryEncoding::Prefix => "prefix",
+            HistoryEncoding::Arithmetic => "arithmetic",
+            HistoryEncoding::Conditioned => "conditioned",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "prefix" => Some(HistoryEncoding::Prefix),
+            "arithmetic" => Some(HistoryEncoding::Arithmetic),
+            "conditioned" => Some(HistoryEncoding::Conditioned),
+            _ => None,
+        }
+    }
+
+    /// Encode `hist` into bytes. Decoding requires the number of operations.
+    pub fn encode(&self, hist: &[Op]) -> Vec<u8> {
+        match self {
+            HistoryEncoding::Arithmetic => Op::encode_arithmetic(hist),
+            _ => self.encode_bits(hist).into_vec(),
+        }
+    }
+
+    fn encode_bits(&self, hist: &[Op]) -> BitVec<u8, Msb0> {
+        let escapes = Op::escapes(hist);
+        let mut bvec = BitVec::repeat(escapes, 1);
+        match self {
+            HistoryEncoding::Conditioned => bvec.extend(Op::encode_conditioned(hist, escapes)),
+            _ => bvec.extend(Op::encode_prefix(hist, escapes)),
+        }
+        bvec
+    }
+
+    /// Encode `hist` so that every component of `components` can be decoded
+    /// on its own, and return the bit offset of each. Arithmetic coding
+    /// starts over on a byte boundary for every component.
+    pub fn encode_components(
+        &self,
+        hist: &[Op],
+        components: &[ComponentEntry],
+    ) -> (Vec<u8>, Vec<usize>) {
+        if components.is_empty() {
+            return (self.encode(hist), Vec::new());
+        }
+        let mut offsets = Vec::with_capacity(components.len());
+        let mut t = 0;
+        match self {
+            HistoryEncoding::Prefix | HistoryEncoding::Conditioned => {
+                // Components end with E, so the conditioned code of the next
+                // one starts like the whole history does
+                let conditioned = *self == HistoryEncoding::Conditioned;
+                let escapes = Op::escapes(hist);
+                let mut bit = 1;
+                let mut previous = Op::E;
+                for c in components {
+                    offsets.push(bit);
+                    for &op in &hist[t..t + c.faces] {
+                        bit += match op {
+                            Op::C => 1,
+                            Op::S | Op::H | Op::M | Op::R if conditioned && previous == Op::C => 2,
+                            _ => 3,
+                        };
+                        if escapes {
+                            bit += op.escape().len();
+                        }
+                        previous = op;
+                    }
+                    t += c.faces;
+                }
+                (self.encode(hist), offsets)
+            }
+            HistoryEncoding::Arithmetic => {
+                let mut bytes = Vec::new();
+                for c in components {
+                    offsets.push(bytes.len() * 8);
+                    bytes.extend(Op::encode_arithmetic(&hist[t..t + c.faces]));
+                    t += c.faces;
+                }
+                (bytes, offsets)
+            }
+        }
+    }
+
+    /// Decode the `count` operations that start at bit `offset`.
+    pub fn decode_at(
+        &self,
+        bytes: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> Result<Vec<Op>, EdgeBreakerError> {
+        let bits = bytes.view_bits::<Msb0>();
+        let escapes = bits.first().is_some_and(|b| *b);
+        if offset > bits.len()
+            || (*self == HistoryEncoding::Arithmetic && !offset.is_multiple_of(8))
+        {
+            return Err(EdgeBreakerError::CorruptHistory(format!(
+                "history offset {offset} is out of place"
+            )));
+        }
+        let ops = match self {
+            HistoryEncoding::Arithmetic => Op::decode_arithmetic(&bytes[offset / 8..], count)?,
+            HistoryEncoding::Prefix => {
+                Op::decode_prefix(bits[offset..].iter().by_vals(), Some(count), escapes)?
+            }
+            HistoryEncoding::Conditioned => {
+                Op::decode_conditioned(bits[offset..].iter().by_vals(), count, escapes)?
+            }
+        };
+
+        if ops.len() != count {
+            return Err(EdgeBreakerError::CorruptHistory(format!(
+                "expected {count} operations, decoded {}",
+                ops.len()
+            )));
+        }
+        Ok(ops)
+    }
+
+    /// Decode a history written by [`HistoryEncoding::encode_components`].
+    pub fn decode_components(
+        &self,
+        bytes: &[u8],
+        components: &[ComponentEntry],
+    ) -> Result<Vec<Op>, EdgeBreakerError> {
+        let mut ops = Vec::new();
+        for c in components {
+            ops.extend(self.decode_at(bytes, c.history_bit, c.faces)?);
+        }
+        Ok(ops)
+    }
+
+    pub fn decode(&self, bytes: &[u8], count: usize) -> Result<Vec<Op>, EdgeBreakerError> {
+        let offset = match self {
+            HistoryEncoding::Arithmetic => 0,
+            _ => bytes.len().min(1),
+        };
+        self.decode_at(bytes, offset, count)
+    }
+}
+
+// ,---------------------------------------------------------------------------
+// | Op: history commands
+// '---------------------------------------------------------------------------
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Op {
@@ -17,7 +400,51 @@ pub enum Op {
 }
 
 impl Op {
-    pub fn encode_history(hist: &[Self]) -> (String, usize) {
+    // Symbol used by arithmetic coding
+    fn symbol(&self) -> usize {
+        match self {
+            Op::C => 0,
+            Op::L => 1,
+            Op::E => 2,
+            Op::R => 3,
+            Op::S => 4,
+            Op::H => 5,
+            Op::M => 6,
+        }
+    }
+
+    fn from_symbol(symbol: usize) -> Op {
+        [Op::C, Op::L, Op::E, Op::R, Op::S, Op::H, Op::M][symbol]
+    }
+
+    fn escapes(hist: &[Self]) -> bool {
+        hist.iter().any(|&op| op == Op::H || op == Op::M)
+    }
+
+    // Bits that follow the code shared by S, H and M
+    fn escape(&self) -> &'static [bool] {
+        match self {
+            Op::S => &[false],
+            Op::H => &[true, false],
+            Op::M => &[true, true],
+            _ => &[],
+        }
+    }
+
+    fn decode_escape(bits: &mut impl Iterator<Item = bool>) -> Result<Op, EdgeBreakerError> {
+        let op = match bits.next() {
+            Some(false) => Some(Op::S),
+            Some(true) => bits.next().map(|m| if m { Op::M } else { Op::H }),
+            None => None,
+        };
+        op.ok_or_else(|| {
+            EdgeBreakerError::CorruptHistory(
+                "history ends in the middle of an operation".to_string(),
+            )
+        })
+    }
+
+    fn encode_prefix(hist: &[Self], escapes: bool) -> BitVec<u8, Msb0> {
         let mut bvec = bitvec![u8, Msb0;];
         for op in hist {
             match op {
@@ -27,28 +454,32 @@ impl Op {
                 Op::L => bvec.extend(&0b110u8.view_bits::<Msb0>()[5..8]),
                 Op::E => bvec.extend(&0b111u8.view_bits::<Msb0>()[5..8]),
             }
+            if escapes {
+                bvec.extend(op.escape());
+            }
         }
-
-        let pad = {
-            let _p = bvec.len() % 8;
-            if _p == 0 { 0 } else { 8 - _p }
-        };
-        (BASE64_STANDARD_NO_PAD.encode(bvec.into_vec()), pad)
+        bvec
     }
 
-    pub fn decode_history(enc: &str, pad: usize) -> Vec<Op> {
+    pub(crate) fn decode_prefix(
+        mut bits: impl Iterator<Item = bool>,
+        count: Option<usize>,
+        escapes: bool,
+    ) -> Result<Vec<Op>, EdgeBreakerError> {
         let mut ops = Vec::new();
-        let bytes = BASE64_STANDARD_NO_PAD.decode(enc).unwrap();
-        let mut bits = bytes.view_bits::<Msb0>().iter();
-        while let Some(b) = bits.next() {
-            if bits.len() < pad {
+        while count.is_none_or(|c| ops.len() < c) {
+            let Some(b) = bits.next() else {
                 break;
-            }
+            };
 
-            if *b {
-                let b1 = bits.next().unwrap();
-                let b2 = bits.next().unwrap();
-                match (*b1, *b2) {
+            if b {
+                let (Some(b1), Some(b2)) = (bits.next(), bits.next()) else {
+                    return Err(EdgeBreakerError::CorruptHistory(
+                        "history ends in the middle of an operation".to_string(),
+                    ));
+                };
+                match (b1, b2) {
+                    (false, false) if escapes => ops.push(Op::decode_escape(&mut bits)?),
                     (false, false) => ops.push(Op::S),
                     (false, true) => ops.push(Op::R),
                     (true, false) => ops.push(Op::L),
@@ -59,10 +490,127 @@ impl Op {
             }
         }
 
-        ops
+        Ok(ops)
+    }
+
+    fn encode_conditioned(hist: &[Self], escapes: bool) -> BitVec<u8, Msb0> {
+        let mut bvec = bitvec![u8, Msb0;];
+        let mut previous = None;
+        for op in hist {
+            match (previous, op) {
+                (Some(Op::C), Op::S | Op::H | Op::M) => {
+                    bvec.extend([true, false]);
+                    if escapes {
+                        bvec.extend(op.escape());
+                    }
+                }
+                (Some(Op::C), Op::R) => bvec.extend([true, true]),
+                _ => bvec.extend(Op::encode_prefix(&[*op], escapes)),
+            }
+            previous = Some(*op);
+        }
+        bvec
+    }
+
+    fn decode_conditioned(
+        mut bits: impl Iterator<Item = bool>,
+        count: usize,
+        escapes: bool,
+    ) -> Result<Vec<Op>, EdgeBreakerError> {
+        // Every operation takes at least one bit, `count` comes from the input
+        let mut ops = Vec::with_capacity(count.min(bits.size_hint().0));
+        while ops.len() < count {
+            let Some(b) = bits.next() else {
+                break;
+            };
+            let op = match (ops.last(), b) {
+                (_, false) => Op::C,
+                (Some(Op::C), true) => match bits.next() {
+                    Some(false) if escapes => Op::decode_escape(&mut bits)?,
+                    Some(false) => Op::S,
+                    Some(true) => Op::R,
+                    None => {
+                        return Err(EdgeBreakerError::CorruptHistory(
+                            "history ends in the middle of an operation".to_string(),
+                        ));
+                    }
+                },
+                (_, true) => {
+                    let bits = [true].into_iter().chain(bits.by_ref());
+                    Op::decode_prefix(bits, Some(1), escapes)?[0]
+                }
+            };
+            ops.push(op);
+        }
+        Ok(ops)
+    }
+
+    fn encode_arithmetic(hist: &[Self]) -> Vec<u8> {
+        let mut models = vec![Model::<SYMBOLS>::default(); CONTEXTS];
+        let mut encoder = Encoder::new();
+        let mut context = START_CONTEXT;
+        for op in hist {
+            let symbol = op.symbol();
+            encoder.encode(&mut models[context], symbol);
+            context = next_context(context, symbol);
+        }
+        encoder.finish()
+    }
+
+    fn decode_arithmetic(bytes: &[u8], count: usize) -> Result<Vec<Op>, EdgeBreakerError> {
+        let mut models = vec![Model::<SYMBOLS>::default(); CONTEXTS];
+        let mut decoder = Decoder::new(bytes);
+        let mut context = START_CONTEXT;
+        // Only a guess, operations can take less than a bit each
+        let mut ops = Vec::with_capacity(count.min(bytes.len().saturating_mul(8)));
+        for _ in 0..count {
+            let symbol = decoder.decode(&mut models[context])?;
+            ops.push(Op::from_symbol(symbol));
+            context = next_context(context, symbol);
+        }
+        Ok(ops)
+    }
+
+    pub fn encode_history(hist: &[Self]) -> (String, usize) {
+        let bvec = HistoryEncoding::Prefix.encode_bits(hist);
+        let pad = {
+            let _p = bvec.len() % 8;
+            if _p == 0 { 0 } else { 8 - _p }
+        };
+        (BASE64_STANDARD_NO_PAD.encode(bvec.into_vec()), pad)
+    }
+
+    pub fn decode_history(enc: &str, pad: usize) -> Result<Vec<Op>, EdgeBreakerError> {
+        let bytes = Op::unpad(enc, pad)?;
+        match bytes.split_first() {
+            Some((escapes, bits)) => Op::decode_prefix(bits.iter().by_vals(), None, *escapes),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub(crate) fn unpad(enc: &str, pad: usize) -> Result<BitVec<u8, Msb0>, EdgeBreakerError> {
+        let bytes = BASE64_STANDARD_NO_PAD
+            .decode(enc)
+            .map_err(|err| EdgeBreakerError::CorruptHistory(format!("invalid base64: {err}")))?;
+        let mut bits = BitVec::from_vec(bytes);
+        let len = bits.len().checked_sub(pad).ok_or_else(|| {
+            EdgeBreakerError::CorruptHistory("padding is longer than the history".to_string())
+        })?;
+        bits.truncate(len);
+        Ok(bits)
     }
 }
 
+// The context of an operation is the pair of the two operations before it,
+// with one extra value for the start of the history.
+const SYMBOLS: usize = 7;
+const CONTEXTS: usize = 8 * 8;
+const START_CONTEXT: usize = 7 * 8 + 7;
+
+fn next_context(context: usize, symbol: usize) -> usize {
+    (context % 8) * 8 + symbol
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,6 +631,81 @@ mod tests {
         let (base64, pad) = Op::encode_history(&ops);
         println!("original: {:?}", ops);
         println!("encoded:  {:?}", base64);
-        println!("decoded:  {:?}", Op::decode_history(&base64, pad));
+        println!("decoded:  {:?}", Op::decode_history(&base64, pad).unwrap());
+    }
+
+    #[test]
+    fn arithmetic_roundtrip() {
+        // Deterministic pseudo-random history
+        let mut state = 7u32;
+        let ops = (0..5000)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                [Op::C, Op::C, Op::C, Op::R, Op::R, Op::S, Op::L, Op::E][(state >> 16) as usize % 8]
+            })
+            .collect::<Vec<_>>();
+
+        for encoding in [HistoryEncoding::Prefix, HistoryEncoding::Arithmetic] {
+            let bytes = encoding.encode(&ops);
+            assert_eq!(encoding.decode(&bytes, ops.len()).unwrap(), ops);
+        }
+    }
+
+    #[test]
+    fn conditioned_code_is_shorter() {
+        let mut state = 11u32;
+        let mut ops = vec![Op::C];
+        for _ in 0..5000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let op = [Op::C, Op::C, Op::R, Op::R, Op::S, Op::L, Op::E][(state >> 16) as usize % 7];
+            // L and E never follow C
+            let after_c = ops.last() == Some(&Op::C);
+            ops.push(if after_c && matches!(op, Op::L | Op::E) {
+                Op::R
+            } else {
+                op
+            });
+        }
+
+        let encoding = HistoryEncoding::Conditioned;
+        let bytes = encoding.encode(&ops);
+        assert_eq!(encoding.decode(&bytes, ops.len()).unwrap(), ops);
+        assert!(bytes.len() < HistoryEncoding::Prefix.encode(&ops).len());
+        assert_eq!(HistoryEncoding::from_name("conditioned"), Some(encoding));
+    }
+
+    #[test]
+    fn escaped_history_and_table_roundtrip() {
+        let ops = vec![
+            Op::C,
+            Op::H,
+            Op::S,
+            Op::C,
+            Op::M,
+            Op::R,
+            Op::L,
+            Op::E,
+            Op::E,
+        ];
+        for encoding in [
+            HistoryEncoding::Prefix,
+            HistoryEncoding::Arithmetic,
+            HistoryEncoding::Conditioned,
+        ] {
+            let bytes = encoding.encode(&ops);
+            assert_eq!(encoding.decode(&bytes, ops.len()).unwrap(), ops);
+        }
+        let (base64, pad) = Op::encode_history(&ops);
+        assert_eq!(Op::decode_history(&base64, pad).unwrap(), ops);
+
+        // An operation count far beyond the input is not reserved up front
+        for encoding in [HistoryEncoding::Arithmetic, HistoryEncoding::Conditioned] {
+            assert!(encoding.decode(&encoding.encode(&ops), usize::MAX).is_err());
+        }
+
+        let table = vec![Table::Hole(0), Table::Merge(3, 1000, 17)];
+        let bytes = Table::encode(&table);
+        assert_eq!(Table::decode(&bytes, &ops).unwrap(), table);
+        assert!(Table::encode(&[]).is_empty());
     }
 }
@@ -0,0 +1,388 @@
+// ,---------------------------------------------------------------------------
+// | Spirale Reversi: decoding the history backwards
+// '---------------------------------------------------------------------------
+
+use super::{EdgeBreaker, public::Op};
+use crate::EdgeBreakerError;
+
+// Operations of one connected component
+struct Component {
+    start: usize,
+    end: usize,
+    /// Number of vertices on the initial boundary
+    boundary: usize,
+    /// Number of vertices added by C and H operations
+    c_count: usize,
+    /// Number of vertices in all earlier components
+    base: usize,
+}
+
+// Split the history into components, the same way the forward decoder does
+fn components(eb: &EdgeBreaker) -> Result<Vec<Component>, EdgeBreakerError> {
+    let mut components = Vec::new();
+    let mut start = 0;
+    let mut base = 0;
+    let mut e: i64 = 0;
+    let mut d = 0;
+    let mut c = 0;
+    let mut lengths = eb.lengths.iter();
+    let mut merges = eb.m_table.len();
+
+    for (i, op) in eb.history.iter().enumerate() {
+        match op {
+            Op::C => {
+                e -= 1;
+                c += 1;
+            }
+            Op::L | Op::R => e += 1,
+            Op::S => {
+                e -= 1;
+                d += 1;
+            }
+            Op::H => {
+                let l = *lengths.next().ok_or_else(|| {
+                    EdgeBreakerError::TableMismatch("H without a hole length".to_string())
+                })?;
+                e -= l as i64 + 1;
+                c += l;
+            }
+            Op::M => {
+                merges = merges.checked_sub(1).ok_or_else(|| {
+                    EdgeBreakerError::TableMismatch("M without a merge entry".to_string())
+                })?;
+                e -= 1;
+                d -= 1;
+            }
+            Op::E => {
+                e += 3;
+                if d > 0 {
+                    d -= 1;
+                    continue;
+                }
+                if e < 1 {
+                    return Err(EdgeBreakerError::CorruptHistory(format!(
+                        "component closes with a boundary of {e} edges"
+                    )));
+                }
+
+                let boundary = e as usize;
+                components.push(Component {
+                    start,
+                    end: i + 1,
+                    boundary,
+                    c_count: c,
+                    base,
+                });
+                base += boundary + c;
+                start = i + 1;
+                e = 0;
+                c = 0;
+                d = 0;
+            }
+        }
+    }
+
+    if start < eb.history.len() {
+        return Err(EdgeBreakerError::CorruptHistory(
+            "history ends in the middle of a component".to_string(),
+        ));
+    }
+    Ok(components)
+}
+
+// Boundary of the part of the mesh that was not decoded yet. Vertices are
+// placeholders until they are identified with each other (through S) or
+// get their label (when their C or the initial boundary is reached).
+#[derive(Default)]
+struct Boundary {
+    end: Vec<usize>,
+    next: Vec<usize>,
+    prev: Vec<usize>,
+    parent: Vec<usize>,
+    label: Vec<Option<usize>>,
+}
+
+impl Boundary {
+    fn vertex(&mut self) -> usize {
+        self.parent.push(self.parent.len());
+        self.label.push(None);
+        self.parent.len() - 1
+    }
+
+    fn edge(&mut self, end: usize) -> usize {
+        self.end.push(end);
+        self.next.push(self.end.len() - 1);
+        self.prev.push(self.end.len() - 1);
+        self.end.len() - 1
+    }
+
+    // Insert a new edge ending at a new vertex before `g`
+    fn insert(&mut self, g: usize) -> usize {
+        let v = self.vertex();
+        let a = self.edge(v);
+        let gp = self.prev[g];
+        self.next[gp] = a;
+        self.prev[a] = gp;
+        self.next[a] = g;
+        self.prev[g] = a;
+        a
+    }
+
+    fn find(&mut self, mut v: usize) -> usize {
+        while self.parent[v] != v {
+            self.parent[v] = self.parent[self.parent[v]];
+            v = self.parent[v];
+        }
+        v
+    }
+
+    fn union(&mut self, a: usize, b: usize) -> Result<(), EdgeBreakerError> {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return Ok(());
+        }
+        self.parent[b] = a;
+        match (self.label[a], self.label[b]) {
+            (Some(x), Some(y)) if x != y => Err(EdgeBreakerError::CorruptHistory(format!(
+                "the history joins the distinct vertices {x} and {y}"
+            ))),
+            (None, y) => {
+                self.label[a] = y;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn set_label(&mut self, v: usize, label: usize) -> Result<(), EdgeBreakerError> {
+        let v = self.find(v);
+        match self.label[v] {
+            Some(other) if other != label => Err(EdgeBreakerError::CorruptHistory(format!(
+                "vertex {label} is also vertex {other}"
+            ))),
+            _ => {
+                self.label[v] = Some(label);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Decode the history of `eb` from the last operation to the first. Every
+/// operation is undone on the boundary of the part that is still missing,
+/// so S needs no offset: the vertex it splits at is found when its two
+/// halves meet again. H and M are undone with their hole length and merge
+/// entry. Produces the same faces as the forward decoder.
+pub fn decompress(eb: &EdgeBreaker) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let components = components(eb)?;
+    let corrupt = |msg: &str| EdgeBreakerError::CorruptHistory(msg.to_string());
+    let mut li = eb.lengths.len();
+    let mut mi = eb.m_table.len();
+
+    let mut b = Boundary::default();
+    let mut tv = Vec::with_capacity(eb.history.len());
+
+    for component in components.iter().rev() {
+        let mut gate: Option<usize> = None;
+        let mut stack = Vec::new();
+        let mut c_label = component.base + component.boundary + component.c_count;
+
+        for op in eb.history[component.start..component.end].iter().rev() {
+            if *op == Op::E {
+                stack.extend(gate);
+                let v = [b.vertex(), b.vertex(), b.vertex()];
+                let e = v.map(|v| b.edge(v));
+                for k in 0..3 {
+                    b.next[e[k]] = e[(k + 1) % 3];
+                    b.prev[e[k]] = e[(k + 2) % 3];
+                }
+                gate = Some(e[1]);
+                tv.push(v);
+                continue;
+            }
+
+            let g = gate.ok_or_else(|| corrupt("operation after the end of a component"))?;
+            match op {
+                Op::C => {
+                    let a = b.prev[g];
+                    let gp = b.prev[a];
+                    if a == g || gp == g {
+                        return Err(corrupt("C on a boundary of fewer than 3 edges"));
+                    }
+                    tv.push([b.end[gp], b.end[g], b.end[a]]);
+                    b.set_label(b.end[a], c_label)?;
+                    c_label -= 1;
+                    b.next[gp] = g;
+                    b.prev[g] = gp;
+                }
+                Op::R => {
+                    let h = b.insert(g);
+                    tv.push([b.end[b.prev[h]], b.end[h], b.end[g]]);
+                    gate = Some(h);
+                }
+                Op::L => {
+                    let h = b.insert(g);
+                    tv.push([b.end[h], b.end[g], b.end[b.prev[h]]]);
+                }
+                Op::S => {
+                    let a = stack
+                        .pop()
+                        .ok_or_else(|| corrupt("S without a matching E"))?;
+                    let gp = b.prev[a];
+                    let dn = b.next[a];
+                    let d = b.prev[g];
+                    b.union(b.end[a], b.end[d])?;
+                    tv.push([b.end[gp], b.end[g], b.end[d]]);
+                    b.next[gp] = g;
+                    b.prev[g] = gp;
+                    b.next[d] = dn;
+                    b.prev[dn] = d;
+                }
+                Op::H => {
+                    li -= 1;
+                    let l = eb.lengths[li];
+                    let mut a = b.prev[g];
+                    let last = a;
+                    for k in (1..=l).rev() {
+                        a = b.prev[a];
+                        if a == g {
+                            return Err(corrupt("H is longer than the boundary"));
+                        }
+                        b.set_label(b.end[a], c_label - l + k)?;
+                    }
+                    b.union(b.end[a], b.end[last])?;
+                    let gp = b.prev[a];
+                    tv.push([b.end[gp], b.end[g], b.end[a]]);
+                    c_label -= l;
+                    b.next[gp] = g;
+                    b.prev[g] = gp;
+                }
+                Op::M => {
+                    mi -= 1;
+                    let (p, o, l) = eb.m_table[mi];
+                    // The forward decoder continues with the top of the
+                    // stack after M, which is the gate we have now
+                    stack.push(g);
+                    let mut d = *stack
+                        .get(p)
+                        .ok_or_else(|| corrupt("M references a missing split"))?;
+                    for _ in 0..o {
+                        d = b.next[d];
+                    }
+                    let g = b.next[d];
+                    let mut dn = d;
+                    for _ in 1..l {
+                        dn = b.prev[dn];
+                    }
+                    let a = b.prev[dn];
+                    let gp = b.prev[a];
+                    if a == d || gp == d {
+                        return Err(corrupt("M merges a loop of the wrong length"));
+                    }
+                    b.union(b.end[a], b.end[d])?;
+                    tv.push([b.end[gp], b.end[g], b.end[d]]);
+                    b.next[gp] = g;
+                    b.prev[g] = gp;
+                    b.next[d] = dn;
+                    b.prev[dn] = d;
+                    gate = Some(g);
+                }
+                Op::E => unreachable!(),
+            }
+        }
+
+        // What is left is the boundary the forward decoder starts with
+        let g = gate.ok_or_else(|| corrupt("empty component"))?;
+        if !stack.is_empty() {
+            return Err(corrupt("E without a matching S"));
+        }
+        let mut e = g;
+        for k in 0..component.boundary {
+            if k > 0 && e == g {
+                return Err(corrupt("initial boundary is too short"));
+            }
+            b.set_label(b.end[e], component.base + k + 1)?;
+            e = b.next[e];
+        }
+        if e != g {
+            return Err(corrupt("initial boundary is too long"));
+        }
+    }
+
+    tv.reverse();
+    let mut faces = Vec::with_capacity(tv.len());
+    for t in tv {
+        let mut f = [0; 3];
+        for k in 0..3 {
+            let v = b.find(t[k]);
+            let label = b.label[v].ok_or_else(|| corrupt("vertex without a label"))?;
+            if label > eb.previous.len() {
+                return Err(EdgeBreakerError::TableMismatch(format!(
+                    "history references {label} vertices, but only {} are known",
+                    eb.previous.len()
+                )));
+            }
+            f[k] = eb.previous[label - 1].offset();
+        }
+        faces.push(f);
+    }
+
+    Ok(faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DecoderKind, DecompressOptions, Mesh, compress, decompress_with,
+        edgebreaker::meshes::{grid, holes, tube},
+    };
+
+    #[test]
+    fn spirale_reversi_matches_forward() {
+        let octahedron = Mesh {
+            vertices: vec![[0.0; 3]; 6],
+            faces: vec![
+                [0, 2, 4],
+                [2, 1, 4],
+                [1, 3, 4],
+                [3, 0, 4],
+                [2, 0, 5],
+                [1, 2, 5],
+                [3, 1, 5],
+                [0, 3, 5],
+            ],
+            ..Default::default()
+        };
+        let strip = Mesh {
+            vertices: vec![[0.0; 3]; 5],
+            faces: vec![[0, 1, 2], [2, 1, 3], [2, 3, 4]],
+            ..Default::default()
+        };
+
+        for mesh in [
+            grid(1),
+            grid(4),
+            grid(9),
+            octahedron,
+            strip,
+            holes(grid(5), 5, &[(2, 2)]),
+            holes(grid(8), 8, &[(1, 1), (2, 1), (5, 5), (5, 6)]),
+            tube(5, 5, false),
+            tube(6, 6, true),
+        ] {
+            let compressed = compress(&mesh).unwrap();
+            let decode = |decoder| {
+                decompress_with(
+                    &compressed,
+                    &DecompressOptions {
+                        decoder,
+                        ..Default::default()
+                    },
+                )
+            };
+            let forward = decode(DecoderKind::Forward).unwrap();
+            let reversi = decode(DecoderKind::SpiraleReversi).unwrap();
+            assert_eq!(reversi.faces, forward.faces);
+        }
+    }
+}
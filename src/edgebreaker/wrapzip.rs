@@ -0,0 +1,420 @@
+// ,---------------------------------------------------------------------------
+// | Wrap&Zip: decoding with placeholder vertices
+// '---------------------------------------------------------------------------
+
+use super::{EdgeBreaker, decompression::split_offsets, public::Op};
+use crate::EdgeBreakerError;
+
+// Free edges of the spanning tree of triangles ("web"). The left edge of a C,
+// the edges of a hole and the two edges of M are glued to triangles decoded
+// later, while the edges R, L and E leave are glued to the boundary that
+// already exists.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Initial,
+    Left,
+    Glued,
+    Gate,
+}
+
+#[derive(Default)]
+struct Web {
+    end: Vec<usize>,
+    next: Vec<usize>,
+    prev: Vec<usize>,
+    kind: Vec<Kind>,
+    parent: Vec<usize>,
+    label: Vec<Option<usize>>,
+}
+
+impl Web {
+    fn vertex(&mut self, label: Option<usize>) -> usize {
+        self.parent.push(self.parent.len());
+        self.label.push(label);
+        self.parent.len() - 1
+    }
+
+    fn edge(&mut self, end: usize, kind: Kind) -> usize {
+        self.end.push(end);
+        self.next.push(self.end.len() - 1);
+        self.prev.push(self.end.len() - 1);
+        self.kind.push(kind);
+        self.end.len() - 1
+    }
+
+    fn link(&mut self, x: usize, y: usize) {
+        self.next[x] = y;
+        self.prev[y] = x;
+    }
+
+    fn find(&mut self, mut v: usize) -> usize {
+        while self.parent[v] != v {
+            self.parent[v] = self.parent[self.parent[v]];
+            v = self.parent[v];
+        }
+        v
+    }
+
+    fn union(&mut self, a: usize, b: usize) -> Result<(), EdgeBreakerError> {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return Ok(());
+        }
+        self.parent[b] = a;
+        match (self.label[a], self.label[b]) {
+            (Some(x), Some(y)) if x != y => Err(EdgeBreakerError::CorruptHistory(format!(
+                "zipping joins the distinct vertices {x} and {y}"
+            ))),
+            (None, y) => {
+                self.label[a] = y;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Glue two free edges running in opposite directions
+    fn zip(&mut self, x: usize, y: usize) -> Result<(), EdgeBreakerError> {
+        self.union(self.end[self.prev[x]], self.end[y])?;
+        self.union(self.end[x], self.end[self.prev[y]])
+    }
+
+    // Walking the free edges of the tree, every glued edge closes the
+    // innermost open edge. Glued edges without one are zipped to the initial
+    // boundary, innermost first. Only holds without handles, whose edges
+    // would cross.
+    fn zip_nested(&mut self, first: usize, bc: usize) -> Result<(), EdgeBreakerError> {
+        let corrupt = |msg: &str| EdgeBreakerError::CorruptHistory(msg.to_string());
+        let mut open = Vec::new();
+        let mut rest = Vec::new();
+        let mut e = self.next[first + bc - 1];
+        loop {
+            match self.kind[e] {
+                Kind::Left => open.push(e),
+                Kind::Glued => match open.pop() {
+                    Some(left) => self.zip(left, e)?,
+                    None => rest.push(e),
+                },
+                Kind::Initial => break,
+                Kind::Gate => return Err(corrupt("gate left after wrapping")),
+            }
+            e = self.next[e];
+        }
+        if !open.is_empty() || rest.len() != bc - 1 {
+            return Err(corrupt("free edges do not zip up"));
+        }
+        for (k, &glued) in rest.iter().rev().enumerate() {
+            self.zip(first + k + 1, glued)?;
+        }
+        Ok(())
+    }
+
+    // Zip every glued edge to the edge it meets on the boundary of the
+    // forward decoder, which is replayed on the free edges. `wrapped` holds
+    // the tip and the two new edges of every triangle.
+    fn zip_along(
+        &mut self,
+        ops: &[Op],
+        wrapped: &[(usize, usize, usize)],
+        offsets: &[usize],
+        merges: &[(usize, usize, usize)],
+        first: usize,
+        bc: usize,
+    ) -> Result<(), EdgeBreakerError> {
+        let mut f = Web {
+            next: vec![0; self.end.len()],
+            prev: vec![0; self.end.len()],
+            ..Default::default()
+        };
+        for k in 0..bc {
+            f.link(first + k, first + (k + 1) % bc);
+        }
+
+        let mut g = first;
+        let mut stack = Vec::new();
+        let (mut s, mut m) = (0, 0);
+        for (op, &(t, l, r)) in ops.iter().zip(wrapped) {
+            let (gp, gn) = (f.prev[g], f.next[g]);
+            match op {
+                Op::C | Op::H => {
+                    f.link(gp, l);
+                    for e in l..r {
+                        f.link(e, e + 1);
+                    }
+                    f.link(r, gn);
+                    g = r;
+                }
+                Op::R => {
+                    self.zip(gn, r)?;
+                    let gnn = f.next[gn];
+                    f.link(gp, l);
+                    f.link(l, gnn);
+                    g = l;
+                }
+                Op::L => {
+                    self.zip(gp, l)?;
+                    let gpp = f.prev[gp];
+                    f.link(gpp, r);
+                    f.link(r, gn);
+                    g = r;
+                }
+                Op::E => {
+                    self.zip(gp, l)?;
+                    self.zip(gn, r)?;
+                    let Some(a) = stack.pop() else { break };
+                    g = a;
+                }
+                Op::S | Op::M => {
+                    let d = if *op == Op::S {
+                        let mut d = gn;
+                        for _ in 0..offsets[s] {
+                            d = f.next[d];
+                        }
+                        s += 1;
+                        d
+                    } else {
+                        let (p, o, _) = merges[m];
+                        m += 1;
+                        let mut d = *stack.get(p).ok_or_else(|| {
+                            EdgeBreakerError::CorruptHistory(format!(
+                                "M references stack position {p} of {}",
+                                stack.len()
+                            ))
+                        })?;
+                        for _ in 0..o {
+                            d = f.next[d];
+                        }
+                        d
+                    };
+                    self.union(t, self.end[d])?;
+                    let dn = f.next[d];
+                    f.link(gp, l);
+                    f.link(l, dn);
+                    f.link(d, r);
+                    f.link(r, gn);
+                    if *op == Op::S {
+                        stack.push(l);
+                        g = r;
+                    } else {
+                        g = stack.pop().ok_or_else(|| {
+                            EdgeBreakerError::CorruptHistory(
+                                "M without a pending split".to_string(),
+                            )
+                        })?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode the history of `eb` in two passes. The triangles are first wrapped
+/// into a tree, each with a new placeholder vertex at its tip (a labelled one
+/// for C). The free edges of the tree are then zipped together, which
+/// identifies the placeholders with each other and the labelled vertices.
+/// Without handles the free edges nest, so S needs no offset. Components
+/// with M are zipped along the boundary of the forward decoder instead.
+pub fn decompress(eb: &EdgeBreaker) -> Result<Vec<[usize; 3]>, EdgeBreakerError> {
+    let corrupt = |msg: &str| EdgeBreakerError::CorruptHistory(msg.to_string());
+    let mut w = Web::default();
+    let mut tv = Vec::with_capacity(eb.history.len());
+    let mut base = 0;
+    let mut i = 0;
+    let (mut li, mut mi) = (0, 0);
+
+    while i < eb.history.len() {
+        // Count the initial boundary of the component
+        let mut e: i64 = 0;
+        let mut d = 0;
+        let (mut holes, mut merges) = (0, 0);
+        let mut end = None;
+        for (j, op) in eb.history.iter().enumerate().skip(i) {
+            match op {
+                Op::C => e -= 1,
+                Op::L | Op::R => e += 1,
+                Op::S => {
+                    e -= 1;
+                    d += 1;
+                }
+                Op::H => {
+                    let l = eb.lengths.get(li + holes).ok_or_else(|| {
+                        EdgeBreakerError::TableMismatch("H without a hole length".to_string())
+                    })?;
+                    e -= *l as i64 + 1;
+                    holes += 1;
+                }
+                Op::M => {
+                    if mi + merges >= eb.m_table.len() {
+                        return Err(EdgeBreakerError::TableMismatch(
+                            "M without a merge entry".to_string(),
+                        ));
+                    }
+                    e -= 1;
+                    d -= 1;
+                    merges += 1;
+                }
+                Op::E if d > 0 => {
+                    e += 3;
+                    d -= 1;
+                }
+                Op::E => {
+                    e += 3;
+                    end = Some(j + 1);
+                    break;
+                }
+            }
+        }
+        let end = end.ok_or_else(|| corrupt("history ends in the middle of a component"))?;
+        if e < 2 {
+            return Err(corrupt("component closes with a boundary of too few edges"));
+        }
+        let bc = e as usize;
+        let ops = &eb.history[i..end];
+        let lengths = &eb.lengths[li..li + holes];
+        let merges = &eb.m_table[mi..mi + merges];
+
+        let first = w.end.len();
+        for k in 0..bc {
+            let v = w.vertex(Some(base + k + 1));
+            w.edge(v, Kind::Initial);
+        }
+        for k in 0..bc {
+            w.next[first + k] = first + (k + 1) % bc;
+            w.prev[first + k] = first + (k + bc - 1) % bc;
+        }
+
+        // Wrap: replace the gate with the two other edges of its triangle
+        let mut vc = base + bc;
+        let mut gate = Some(first);
+        let mut stack = Vec::new();
+        let mut wrapped = Vec::with_capacity(ops.len());
+        let mut hi = 0;
+        for op in ops {
+            let g = gate.ok_or_else(|| corrupt("operation after the end of a component"))?;
+            let (a, b) = (w.end[w.prev[g]], w.end[g]);
+            let t = match op {
+                Op::C => {
+                    vc += 1;
+                    w.vertex(Some(vc))
+                }
+                Op::H => w.vertex(Some(vc + 1)),
+                _ => w.vertex(None),
+            };
+            tv.push([a, b, t]);
+
+            let (left, right) = match op {
+                Op::C | Op::H => (Kind::Left, Kind::Gate),
+                Op::R => (Kind::Gate, Kind::Glued),
+                Op::L => (Kind::Glued, Kind::Gate),
+                Op::E => (Kind::Glued, Kind::Glued),
+                Op::S => (Kind::Gate, Kind::Gate),
+                Op::M => (Kind::Left, Kind::Left),
+            };
+            let (gp, gn) = (w.prev[g], w.next[g]);
+            let l = w.edge(t, left);
+            w.link(gp, l);
+
+            // The hole hangs off the tip, between the two edges
+            let mut last = l;
+            if *op == Op::H {
+                let len = lengths[hi];
+                hi += 1;
+                for k in 1..=len {
+                    let v = if k < len {
+                        w.vertex(Some(vc + k + 1))
+                    } else {
+                        t
+                    };
+                    let h = w.edge(v, Kind::Left);
+                    w.link(last, h);
+                    last = h;
+                }
+                vc += len;
+            }
+
+            let r = w.edge(b, right);
+            w.link(last, r);
+            w.link(r, gn);
+            wrapped.push((t, l, r));
+
+            gate = match op {
+                Op::C | Op::L | Op::H => Some(r),
+                Op::R => Some(l),
+                Op::S => {
+                    stack.push(l);
+                    Some(r)
+                }
+                _ => stack.pop(),
+            };
+        }
+
+        if merges.is_empty() {
+            w.zip_nested(first, bc)?;
+        } else {
+            let offsets = split_offsets(ops, lengths, merges)?;
+            w.zip_along(ops, &wrapped, &offsets, merges, first, bc)?;
+        }
+
+        base = vc;
+        i = end;
+        li += lengths.len();
+        mi += merges.len();
+    }
+    let mut faces = Vec::with_capacity(tv.len());
+    for t in tv {
+        let mut f = [0; 3];
+        for k in 0..3 {
+            let v = w.find(t[k]);
+            let label = w.label[v].ok_or_else(|| corrupt("vertex without a label"))?;
+            if label > eb.previous.len() {
+                return Err(EdgeBreakerError::TableMismatch(format!(
+                    "history references {label} vertices, but only {} are known",
+                    eb.previous.len()
+                )));
+            }
+            f[k] = eb.previous[label - 1].offset();
+        }
+        faces.push(f);
+    }
+
+    Ok(faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        DecoderKind, DecompressOptions, compress, decompress_with,
+        edgebreaker::meshes::{holes, random_grid, tube},
+    };
+
+    #[test]
+    fn wrap_zip_matches_other_decoders() {
+        let meshes = [(1, 0), (3, 1), (8, 2), (8, 3), (20, 4)]
+            .map(|(n, seed)| random_grid(n, seed))
+            .into_iter()
+            .chain([
+                holes(random_grid(5, 5), 5, &[(2, 2)]),
+                holes(random_grid(8, 6), 8, &[(1, 1), (2, 1), (5, 5), (5, 6)]),
+                tube(5, 5, false),
+                tube(6, 6, true),
+            ]);
+        for mesh in meshes {
+            let compressed = compress(&mesh).unwrap();
+            let decode = |decoder| {
+                decompress_with(
+                    &compressed,
+                    &DecompressOptions {
+                        decoder,
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .faces
+            };
+            let faces = decode(DecoderKind::WrapZip);
+            assert_eq!(faces, decode(DecoderKind::Forward));
+            assert_eq!(faces, decode(DecoderKind::SpiraleReversi));
+        }
+    }
+}
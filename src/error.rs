@@ -0,0 +1,41 @@
+use std::{fmt::Display, io};
+
+#[derive(Debug)]
+pub enum EdgeBreakerError {
+    /// The input could not be parsed or references data that does not exist.
+    MalformedInput(String),
+    /// The operation history is inconsistent and cannot be decoded.
+    CorruptHistory(String),
+    /// The `H`/`M` table or the duplicate list does not match the history.
+    TableMismatch(String),
+    /// The mesh has a configuration the encoder cannot traverse.
+    UnsupportedTopology(String),
+    Io(io::Error),
+}
+
+impl Display for EdgeBreakerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdgeBreakerError::MalformedInput(msg) => write!(f, "malformed input: {msg}"),
+            EdgeBreakerError::CorruptHistory(msg) => write!(f, "corrupt history: {msg}"),
+            EdgeBreakerError::TableMismatch(msg) => write!(f, "table mismatch: {msg}"),
+            EdgeBreakerError::UnsupportedTopology(msg) => write!(f, "unsupported topology: {msg}"),
+            EdgeBreakerError::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for EdgeBreakerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EdgeBreakerError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EdgeBreakerError {
+    fn from(err: io::Error) -> Self {
+        EdgeBreakerError::Io(err)
+    }
+}
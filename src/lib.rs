@@ -0,0 +1,91 @@
+//! Edgebreaker vertex connectivity compression.
+//!
+//! ```
+//! use edge_breaker::{Mesh, compress, decompress};
+//!
+//! let mesh = Mesh {
+//!     vertices: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+//!     faces: vec![[0, 1, 2]],
+//!     ..Default::default()
+//! };
+//! let compressed = compress(&mesh)?;
+//! let restored = decompress(&compressed)?;
+//! # Ok::<(), edge_breaker::EdgeBreakerError>(())
+//! ```
+
+pub mod binary;
+mod check;
+pub mod edgebreaker;
+mod error;
+mod mesh;
+pub mod obj;
+pub mod ply;
+mod stats;
+pub mod stl;
+mod verify;
+pub mod weld;
+
+pub use check::{Check, Component, check};
+pub use edgebreaker::public::{
+    ComponentEntry, CompressOptions, Compressed, DecoderKind, DecompressOptions, HistoryEncoding,
+    Op, Table,
+};
+pub use error::EdgeBreakerError;
+pub use mesh::{Mesh, ScalarKind, VertexProperty};
+pub use stats::{Stats, compress_with_stats};
+pub use verify::{Verification, verify};
+
+/// Compress the connectivity of `mesh`. Vertices are reordered into traversal
+/// order and stored alongside the Edgebreaker history.
+pub fn compress(mesh: &Mesh) -> Result<Compressed, EdgeBreakerError> {
+    compress_with(mesh, &CompressOptions::default())
+}
+
+/// Like [`compress`], but with explicit [`CompressOptions`].
+pub fn compress_with(
+    mesh: &Mesh,
+    options: &CompressOptions,
+) -> Result<Compressed, EdgeBreakerError> {
+    edgebreaker::compress_mesh(mesh, options)
+}
+
+/// Restore a mesh from the output of [`compress`]. Face indices refer to
+/// `compressed.vertices`.
+pub fn decompress(compressed: &Compressed) -> Result<Mesh, EdgeBreakerError> {
+    decompress_with(compressed, &DecompressOptions::default())
+}
+
+/// Like [`decompress`], but with explicit [`DecompressOptions`].
+pub fn decompress_with(
+    compressed: &Compressed,
+    options: &DecompressOptions,
+) -> Result<Mesh, EdgeBreakerError> {
+    edgebreaker::decompress_mesh(compressed, options)
+}
+
+/// Decode only component `i` of the component directory, which
+/// [`CompressOptions::threads`] adds. Face indices refer to the vertices of
+/// the returned mesh.
+pub fn decompress_component(
+    compressed: &Compressed,
+    i: usize,
+    options: &DecompressOptions,
+) -> Result<Mesh, EdgeBreakerError> {
+    edgebreaker::decompress_component(compressed, i, options)
+}
+
+/// Decode the faces of `compressed` one at a time and pass each to `emit`,
+/// in the same order and with the same indices as [`decompress_with`]. With
+/// the forward decoder no face is kept after it was emitted, which keeps
+/// memory low for large meshes. Vertices are `compressed.vertices` unless
+/// the geometry is quantized.
+pub fn decompress_faces<F>(
+    compressed: &Compressed,
+    options: &DecompressOptions,
+    emit: F,
+) -> Result<(), EdgeBreakerError>
+where
+    F: FnMut([usize; 3]) -> Result<(), EdgeBreakerError>,
+{
+    edgebreaker::decompress_faces(compressed, options, emit)
+}
@@ -7,28 +7,89 @@ use std::{
 
 use colored::Colorize;
 use debug::Logger;
+use edge_breaker::{
+    CompressOptions, Compressed, DecoderKind, DecompressOptions, EdgeBreakerError, HistoryEncoding,
+    Mesh, binary,
+    obj::Obj,
+    ply::{Ply, PlyFormat},
+    stl::Stl,
+};
 use log::{LevelFilter, error};
-use obj::Obj;
 
 mod debug;
-mod edgebreaker;
-mod obj;
 
 static LOGGER: Logger = Logger;
 
 enum Operation {
     Compress,
     Decompress,
+    Verify,
+    Stats,
+    Check,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Obj,
+    Ply(PlyFormat),
+    Stl,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "obj" => Some(Format::Obj),
+            "ply" => Some(Format::Ply(PlyFormat::BinaryLittleEndian)),
+            "ply-ascii" => Some(Format::Ply(PlyFormat::Ascii)),
+            "stl" => Some(Format::Stl),
+            _ => None,
+        }
+    }
+
+    fn from_path(path: &str) -> Self {
+        match path.rsplit_once('.') {
+            Some((_, ext)) if ext.eq_ignore_ascii_case("ply") => {
+                Format::Ply(PlyFormat::BinaryLittleEndian)
+            }
+            Some((_, ext)) if ext.eq_ignore_ascii_case("stl") => Format::Stl,
+            _ => Format::Obj,
+        }
+    }
+
+    // `weld` is the tolerance for merging STL corners into shared vertices.
+    // Other formats are welded while compressing.
+    fn read(self, reader: &mut dyn BufRead, weld: f32) -> Result<Mesh, EdgeBreakerError> {
+        Ok(match self {
+            Format::Obj => Mesh::from(Obj::read(reader)?),
+            Format::Ply(_) => Mesh::from(Ply::read(reader)?),
+            Format::Stl => Stl::read(reader)?.to_mesh(weld),
+        })
+    }
+
+    fn write(self, mesh: Mesh, writer: &mut dyn Write) -> io::Result<()> {
+        match self {
+            Format::Obj => Obj::from(mesh).write(writer),
+            Format::Ply(format) => Ply::from(mesh).write(writer, format),
+            Format::Stl => Stl::from(mesh).write(writer),
+        }
+    }
 }
 
-struct CLI {
+struct Cli {
     verbose: bool,
     input: Option<String>,
     output: Option<String>,
     operation: Option<Operation>,
+    options: CompressOptions,
+    decompress_options: DecompressOptions,
+    binary: bool,
+    format: Option<Format>,
+    weld: f32,
+    stats: bool,
+    json: bool,
 }
 
-impl CLI {
+impl Cli {
     fn open_input(&self) -> Box<dyn BufRead> {
         match &self.input {
             Some(path) => Box::new(BufReader::new(File::open(path).unwrap_or_else(|_| {
@@ -48,12 +109,30 @@ impl CLI {
             None => Box::new(LineWriter::new(io::stdout())),
         }
     }
+
+    fn input_size(&self) -> Option<usize> {
+        let metadata = std::fs::metadata(self.input.as_ref()?).ok()?;
+        Some(metadata.len() as usize)
+    }
+
+    // Format of the uncompressed mesh read from or written to `path`
+    fn mesh_format(&self, path: &Option<String>) -> Format {
+        self.format
+            .or_else(|| path.as_deref().map(Format::from_path))
+            .unwrap_or(Format::Obj)
+    }
 }
 
 fn print_help() {
     eprintln!(
         "usage: {} <{}> [{}]",
-        args().nth(0).unwrap().split('/').last().unwrap().yellow(),
+        args()
+            .next()
+            .unwrap()
+            .split('/')
+            .next_back()
+            .unwrap()
+            .yellow(),
         "OPERATIONS".green(),
         "FLAGS".blue()
     );
@@ -61,29 +140,85 @@ fn print_help() {
     eprintln!("{}:", "OPERATIONS".green());
     eprintln!("  compress       Compress input and write it to output");
     eprintln!("  decompress     Decompress input and write it to output");
+    eprintln!("  verify         Round-trip input and report any differences");
+    eprintln!("  stats          Compress input and report statistics instead of the output");
+    eprintln!("  check          Report problems and topology of the input mesh");
     eprintln!();
     eprintln!("{}:", "FLAGS".blue());
     eprintln!("  -i <file>      Input file. Defaults to stdin");
     eprintln!("  -o <file>      Output file. Defaults to stdout");
+    eprintln!("  -e <encoding>  History encoding: prefix (default), arithmetic, conditioned");
+    eprintln!("  -q <bits>      Quantize vertex positions to this many bits (1-32)");
+    eprintln!("  -b             Write compressed output in the binary container format");
+    eprintln!("  -j <threads>   Compress and decompress connected components in parallel");
     eprintln!("  -v             Increase verbosity");
+    eprintln!("  --decoder <d>  Decoder: forward (default), spirale-reversi, wrap-zip");
+    eprintln!(
+        "  --format <fmt> Mesh format: obj, ply, ply-ascii, stl. Defaults to the file extension"
+    );
+    eprintln!("  --fix-orientation  Keep the corrected winding of inconsistently oriented faces");
+    eprintln!("  --json         Print the check report as JSON");
+    eprintln!("  --triangulate  Compress polygons as plain triangles");
+    eprintln!("  --stats        Print statistics to stderr when compressing");
+    eprintln!("  --restore-welded  Put welded vertices back when decompressing");
+    eprintln!("  --weld <tol>   Merge vertices closer than this. STL input is always welded,");
+    eprintln!("                 by default only exact matches");
     eprintln!();
 }
 
-fn parse_args(args: &mut Args) -> CLI {
-    let mut cli = CLI {
+fn parse_args(args: &mut Args) -> Cli {
+    let mut cli = Cli {
         verbose: false,
         input: None,
         output: None,
         operation: None,
+        options: CompressOptions::default(),
+        decompress_options: DecompressOptions::default(),
+        binary: false,
+        format: None,
+        weld: 0.0,
+        stats: false,
+        json: false,
     };
 
     while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--") {
+            match name {
+                "decoder" => match args.next().as_deref().map(DecoderKind::from_name) {
+                    Some(Some(decoder)) => cli.decompress_options.decoder = decoder,
+                    Some(None) => error!("--decoder: unknown decoder"),
+                    None => error!("--decoder: missing decoder"),
+                },
+                "format" => match args.next().as_deref().map(Format::from_name) {
+                    Some(Some(format)) => cli.format = Some(format),
+                    Some(None) => error!("--format: unknown mesh format"),
+                    None => error!("--format: missing mesh format"),
+                },
+                "fix-orientation" => cli.options.fix_orientation = true,
+                "json" => cli.json = true,
+                "triangulate" => cli.options.triangulate = true,
+                "stats" => cli.stats = true,
+                "restore-welded" => cli.options.restore_welded = true,
+                "weld" => match args.next().map(|tolerance| tolerance.parse()) {
+                    Some(Ok(tolerance)) => {
+                        cli.weld = tolerance;
+                        cli.options.weld = Some(tolerance);
+                    }
+                    Some(Err(_)) => error!("--weld: invalid tolerance"),
+                    None => error!("--weld: missing tolerance"),
+                },
+                _ => error!("Unknown flag '{}'", arg),
+            }
+            continue;
+        }
+
         let mut arg_chars = arg.chars();
         match arg_chars.next() {
             Some('-') => {
-                while let Some(ch) = arg_chars.next() {
+                for ch in arg_chars.by_ref() {
                     match ch {
                         'v' => cli.verbose = true,
+                        'b' => cli.binary = true,
                         'i' => {
                             if let Some(path) = args.next() {
                                 cli.input = Some(path);
@@ -98,6 +233,24 @@ fn parse_args(args: &mut Args) -> CLI {
                                 error!("-o: missing file path")
                             }
                         }
+                        'e' => match args.next().as_deref().map(HistoryEncoding::from_name) {
+                            Some(Some(encoding)) => cli.options.encoding = encoding,
+                            Some(None) => error!("-e: unknown history encoding"),
+                            None => error!("-e: missing history encoding"),
+                        },
+                        'q' => match args.next().map(|bits| bits.parse()) {
+                            Some(Ok(bits)) => cli.options.quantization = Some(bits),
+                            Some(Err(_)) => error!("-q: invalid bit depth"),
+                            None => error!("-q: missing bit depth"),
+                        },
+                        'j' => match args.next().map(|threads| threads.parse()) {
+                            Some(Ok(threads)) => {
+                                cli.options.threads = threads;
+                                cli.decompress_options.threads = threads;
+                            }
+                            Some(Err(_)) => error!("-j: invalid thread count"),
+                            None => error!("-j: missing thread count"),
+                        },
                         _ => error!("Unknown flag '{}'", ch),
                     }
                 }
@@ -106,6 +259,8 @@ fn parse_args(args: &mut Args) -> CLI {
             Some('c') => {
                 if "compression".starts_with(&arg) {
                     cli.operation = Some(Operation::Compress)
+                } else if "check".starts_with(&arg) {
+                    cli.operation = Some(Operation::Check)
                 } else {
                     error!("Unknown operation '{}'", arg);
                 }
@@ -119,6 +274,22 @@ fn parse_args(args: &mut Args) -> CLI {
                 }
             }
 
+            Some('s') => {
+                if "stats".starts_with(&arg) {
+                    cli.operation = Some(Operation::Stats)
+                } else {
+                    error!("Unknown operation '{}'", arg);
+                }
+            }
+
+            Some('v') => {
+                if "verify".starts_with(&arg) {
+                    cli.operation = Some(Operation::Verify)
+                } else {
+                    error!("Unknown operation '{}'", arg);
+                }
+            }
+
             _ => error!("Failed to parse argument '{}'", arg),
         }
     }
@@ -126,6 +297,80 @@ fn parse_args(args: &mut Args) -> CLI {
     cli
 }
 
+fn run(cli: &Cli) -> Result<(), EdgeBreakerError> {
+    match cli.operation {
+        Some(Operation::Compress) => {
+            let mesh = cli
+                .mesh_format(&cli.input)
+                .read(&mut cli.open_input(), cli.weld)?;
+            let compressed = if cli.stats {
+                let (compressed, mut stats) =
+                    edge_breaker::compress_with_stats(&mesh, &cli.options)?;
+                stats.input_size = cli.input_size();
+                eprintln!("{stats}");
+                compressed
+            } else {
+                edge_breaker::compress_with(&mesh, &cli.options)?
+            };
+            if cli.binary {
+                compressed.write_binary(&mut cli.open_output())?;
+            } else {
+                Obj::from(compressed).write(&mut cli.open_output())?;
+            }
+        }
+        Some(Operation::Decompress) => {
+            let mut input = cli.open_input();
+            let compressed = if binary::is_binary(&mut input)? {
+                Compressed::read_binary(&mut input)?
+            } else {
+                Compressed::from(Obj::read(&mut input)?)
+            };
+            let options = &cli.decompress_options;
+            match cli.mesh_format(&cli.output) {
+                Format::Obj => {
+                    Obj::write_decompressed(&compressed, options, &mut cli.open_output())?
+                }
+                format => {
+                    let mesh = edge_breaker::decompress_with(&compressed, options)?;
+                    format.write(mesh, &mut cli.open_output())?;
+                }
+            }
+        }
+        Some(Operation::Verify) => {
+            let mesh = cli
+                .mesh_format(&cli.input)
+                .read(&mut cli.open_input(), cli.weld)?;
+            let report = edge_breaker::verify(&mesh, &cli.options)?;
+            writeln!(cli.open_output(), "{report}")?;
+            if !report.is_ok() {
+                exit(1);
+            }
+        }
+        Some(Operation::Stats) => {
+            let mesh = cli
+                .mesh_format(&cli.input)
+                .read(&mut cli.open_input(), cli.weld)?;
+            let (_, mut stats) = edge_breaker::compress_with_stats(&mesh, &cli.options)?;
+            stats.input_size = cli.input_size();
+            writeln!(cli.open_output(), "{stats}")?;
+        }
+        Some(Operation::Check) => {
+            let mesh = cli
+                .mesh_format(&cli.input)
+                .read(&mut cli.open_input(), cli.weld)?;
+            let report = edge_breaker::check(&mesh);
+            if cli.json {
+                writeln!(cli.open_output(), "{}", report.to_json())?;
+            } else {
+                writeln!(cli.open_output(), "{report}")?;
+            }
+        }
+        None => print_help(),
+    };
+
+    Ok(())
+}
+
 fn main() -> std::io::Result<()> {
     let cli = parse_args(&mut args());
 
@@ -137,19 +382,10 @@ fn main() -> std::io::Result<()> {
         })
     });
 
-    match cli.operation {
-        Some(Operation::Compress) => {
-            let mut obj = Obj::read(&mut cli.open_input());
-            edgebreaker::compress_obj(&mut obj);
-            obj.write(&mut cli.open_output());
-        }
-        Some(Operation::Decompress) => {
-            let mut obj = Obj::read(&mut cli.open_input());
-            edgebreaker::decompress_obj(&mut obj);
-            obj.write(&mut cli.open_output());
-        }
-        None => print_help(),
-    };
+    if let Err(err) = run(&cli) {
+        error!("{err}");
+        exit(1);
+    }
 
     Ok(())
 }
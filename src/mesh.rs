@@ -0,0 +1,131 @@
+// ,---------------------------------------------------------------------------
+// | Mesh: uncompressed triangle mesh
+// '---------------------------------------------------------------------------
+
+/// Triangle mesh with zero-based vertex indices.
+///
+/// Texture coordinates and normals are indexed per corner like in OBJ files:
+/// `face_texcoords[i][k]` is the texture coordinate of corner `k` of
+/// `faces[i]`. The per-corner lists are either empty or as long as `faces`.
+/// `properties` hold any further per-vertex data.
+///
+/// Polygons with more than three corners are stored as fans of triangles.
+/// `polygons` holds the number of corners of every polygon in order, so that
+/// a polygon with `n` corners is made of the next `n - 2` faces. It is empty
+/// when every face is a triangle of its own.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<[f32; 3]>,
+    pub faces: Vec<[usize; 3]>,
+    pub texcoords: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    pub face_texcoords: Vec<[usize; 3]>,
+    pub face_normals: Vec<[usize; 3]>,
+    pub properties: Vec<VertexProperty>,
+    pub polygons: Vec<usize>,
+}
+
+/// Extra per-vertex data such as colors or scanner confidence, carried
+/// through compression unchanged. `values` has one entry per vertex.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexProperty {
+    pub name: String,
+    pub kind: ScalarKind,
+    pub values: Vec<f64>,
+}
+
+/// Storage type of a [`VertexProperty`], named after the PLY scalar types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarKind {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarKind {
+    pub const ALL: [ScalarKind; 8] = [
+        ScalarKind::I8,
+        ScalarKind::U8,
+        ScalarKind::I16,
+        ScalarKind::U16,
+        ScalarKind::I32,
+        ScalarKind::U32,
+        ScalarKind::F32,
+        ScalarKind::F64,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalarKind::I8 => "char",
+            ScalarKind::U8 => "uchar",
+            ScalarKind::I16 => "short",
+            ScalarKind::U16 => "ushort",
+            ScalarKind::I32 => "int",
+            ScalarKind::U32 => "uint",
+            ScalarKind::F32 => "float",
+            ScalarKind::F64 => "double",
+        }
+    }
+
+    /// Accepts both the classic and the sized PLY names (`uchar`, `uint8`).
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarKind::I8),
+            "uchar" | "uint8" => Some(ScalarKind::U8),
+            "short" | "int16" => Some(ScalarKind::I16),
+            "ushort" | "uint16" => Some(ScalarKind::U16),
+            "int" | "int32" => Some(ScalarKind::I32),
+            "uint" | "uint32" => Some(ScalarKind::U32),
+            "float" | "float32" => Some(ScalarKind::F32),
+            "double" | "float64" => Some(ScalarKind::F64),
+            _ => None,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            ScalarKind::I8 | ScalarKind::U8 => 1,
+            ScalarKind::I16 | ScalarKind::U16 => 2,
+            ScalarKind::I32 | ScalarKind::U32 | ScalarKind::F32 => 4,
+            ScalarKind::F64 => 8,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, ScalarKind::F32 | ScalarKind::F64)
+    }
+
+    /// Little endian representation of `value` converted to this type.
+    pub fn to_le_bytes(self, value: f64) -> Vec<u8> {
+        match self {
+            ScalarKind::I8 => (value as i8).to_le_bytes().to_vec(),
+            ScalarKind::U8 => (value as u8).to_le_bytes().to_vec(),
+            ScalarKind::I16 => (value as i16).to_le_bytes().to_vec(),
+            ScalarKind::U16 => (value as u16).to_le_bytes().to_vec(),
+            ScalarKind::I32 => (value as i32).to_le_bytes().to_vec(),
+            ScalarKind::U32 => (value as u32).to_le_bytes().to_vec(),
+            ScalarKind::F32 => (value as f32).to_le_bytes().to_vec(),
+            ScalarKind::F64 => value.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// Inverse of [`ScalarKind::to_le_bytes`], `bytes` must hold
+    /// [`ScalarKind::size`] bytes.
+    pub fn from_le_bytes(self, bytes: &[u8]) -> f64 {
+        match self {
+            ScalarKind::I8 => bytes[0] as i8 as f64,
+            ScalarKind::U8 => bytes[0] as f64,
+            ScalarKind::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarKind::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarKind::I32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarKind::U32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarKind::F32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            ScalarKind::F64 => f64::from_le_bytes(bytes[..8].try_into().unwrap()),
+        }
+    }
+}
@@ -1,43 +1,104 @@
+use base64::{Engine, prelude::BASE64_STANDARD_NO_PAD};
 use log::warn;
 use std::{
     fmt::Debug,
-    io::{BufRead, Write},
+    io::{self, BufRead, Write},
+    str::FromStr,
 };
 
-use crate::edgebreaker::public::Op;
+use crate::{
+    DecompressOptions, EdgeBreakerError, decompress_faces, decompress_with,
+    edgebreaker::public::{
+        Attribute, ComponentEntry, Compressed, HistoryEncoding, Op, QuantizedGeometry, Table,
+    },
+    mesh::{Mesh, ScalarKind, VertexProperty},
+};
 
-#[derive(Debug, Clone, Copy)]
-pub enum Table {
-    Hole(usize, usize),
-    Merge(usize, usize, usize, usize),
-}
+// Layout of the eb lines, which follows the binary container. Files without
+// an `ebver` line have the original layout, see `expand_baseline`.
+const VERSION: usize = 4;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Obj {
     pub vertices: Vec<[f32; 3]>,
     pub faces: Vec<[usize; 3]>,
+    pub texcoords: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    pub face_texcoords: Vec<[usize; 3]>,
+    pub face_normals: Vec<[usize; 3]>,
+    /// Number of corners of every face line, see [`Mesh::polygons`]
+    pub polygons: Vec<usize>,
     pub eb_history: Vec<Op>,
     pub eb_table: Vec<Table>,
     pub eb_dup: Vec<(usize, usize)>,
+    pub eb_encoding: HistoryEncoding,
+    pub eb_geometry: Option<QuantizedGeometry>,
+    pub eb_texcoords: Option<Vec<u8>>,
+    pub eb_normals: Option<Vec<u8>>,
+    pub eb_properties: Vec<VertexProperty>,
+    pub eb_flipped: Vec<usize>,
+    pub eb_welded: Vec<(usize, usize)>,
+    pub eb_polygons: Option<Vec<u8>>,
+    pub eb_components: Vec<ComponentEntry>,
 }
 
 impl Obj {
-    pub fn read<T: BufRead>(reader: &mut T) -> Self {
+    pub fn read<T: BufRead + ?Sized>(reader: &mut T) -> Result<Self, EdgeBreakerError> {
         let mut vertices = Vec::new();
         let mut faces = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut normals = Vec::new();
+        let mut face_texcoords = Vec::new();
+        let mut face_normals = Vec::new();
+        let mut polygons = Vec::new();
+        let mut eb_texcoords = None;
+        let mut eb_normals = None;
+        let mut eb_properties = Vec::new();
+        let mut eb_flipped = Vec::new();
+        let mut eb_welded = Vec::new();
+        let mut eb_polygons = None;
+        let mut eb_components = Vec::new();
+        let mut baseline = true;
         let mut history = Vec::new();
-        let mut table = Vec::new();
+        let mut table = None;
+        let mut baseline_table = Vec::new();
         let mut dup = Vec::new();
+        let mut encoding = HistoryEncoding::Prefix;
+        let mut geometry = None;
 
-        #[inline(always)]
-        fn parse_index(word: &str) -> usize {
-            word.split('/').nth(0).unwrap().parse().unwrap()
-        }
-
-        for (i, line) in reader.lines().map_while(Result::ok).enumerate() {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
             let mut chars = line.chars();
             let c = chars.next();
 
+            #[inline(always)]
+            fn parse<T: FromStr>(word: &str, i: usize) -> Result<T, EdgeBreakerError> {
+                word.parse().map_err(|_| {
+                    EdgeBreakerError::MalformedInput(format!("invalid value '{word}' at line {i}"))
+                })
+            }
+
+            // Vertex, texture coordinate and normal index of a face corner
+            #[inline(always)]
+            fn parse_corner(word: &str, i: usize) -> Result<[Option<usize>; 3], EdgeBreakerError> {
+                let mut indices = [None; 3];
+                for (index, w) in indices.iter_mut().zip(word.split('/')) {
+                    if !w.is_empty() {
+                        *index = Some(parse(w, i)?);
+                    }
+                }
+                Ok(indices)
+            }
+
+            #[inline(always)]
+            fn parse_floats(line: &str, i: usize) -> Result<Vec<f32>, EdgeBreakerError> {
+                line.split(' ')
+                    .skip(1)
+                    .filter(|x| !x.is_empty())
+                    .map(|w| parse(w, i))
+                    .collect()
+            }
+
             match c {
                 Some('v') => match chars.next() {
                     Some(' ') => {
@@ -45,42 +106,113 @@ impl Obj {
                             .split(' ')
                             .skip(1)
                             .filter(|x| !x.is_empty())
-                            .map(|w| w.parse().unwrap())
-                            .collect::<Vec<_>>()
+                            .map(|w| parse(w, i))
+                            .collect::<Result<Vec<_>, _>>()?
                             .try_into()
-                            .unwrap();
+                            .map_err(|_| {
+                                EdgeBreakerError::MalformedInput(format!(
+                                    "vertex at line {i} does not have 3 coordinates"
+                                ))
+                            })?;
                         vertices.push(vals);
                     }
+                    // The optional w coordinate is dropped
+                    Some('t') => match parse_floats(&line, i)?[..] {
+                        [u, v] | [u, v, _] => texcoords.push([u, v]),
+                        _ => {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "texture coordinate at line {i} does not have 2 coordinates"
+                            )));
+                        }
+                    },
+                    Some('n') => match parse_floats(&line, i)?[..] {
+                        [x, y, z] => normals.push([x, y, z]),
+                        _ => {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "normal at line {i} does not have 3 coordinates"
+                            )));
+                        }
+                    },
                     _ => continue,
                 },
 
                 Some('f') => {
-                    let vals = line
+                    let corners = line
                         .split(' ')
                         .skip(1)
                         .filter(|x| !x.is_empty())
-                        .map(parse_index)
-                        .collect::<Vec<_>>();
+                        .map(|w| parse_corner(w, i))
+                        .collect::<Result<Vec<_>, _>>()?;
 
-                    let n = vals.len();
-                    if n == 3 {
-                        faces.push(vals.try_into().unwrap());
-                        continue;
+                    let n = corners.len();
+                    if n < 3 {
+                        return Err(EdgeBreakerError::MalformedInput(format!(
+                            "face at line {i} has fewer than 3 vertices"
+                        )));
                     }
+                    polygons.push(n);
 
-                    for i in 1..=n - 2 {
-                        faces.push([vals[0], vals[i], vals[i + 1]]);
+                    for (k, target) in [&mut faces, &mut face_texcoords, &mut face_normals]
+                        .into_iter()
+                        .enumerate()
+                    {
+                        let vals = corners.iter().map(|c| c[k]).collect::<Option<Vec<_>>>();
+                        match vals {
+                            Some(vals) => {
+                                for i in 1..=n - 2 {
+                                    target.push([vals[0], vals[i], vals[i + 1]]);
+                                }
+                            }
+                            None if k == 0 || corners.iter().any(|c| c[k].is_some()) => {
+                                return Err(EdgeBreakerError::MalformedInput(format!(
+                                    "face at line {i} has corners with missing indices"
+                                )));
+                            }
+                            None => {}
+                        }
                     }
                 }
 
                 Some('e') => match line.split(' ').nth(0).unwrap() {
-                    "ebh" => {
-                        let [base64, pad_char] = line.split(' ').skip(1).collect::<Vec<_>>()[..]
-                        else {
-                            warn!("Failed decoding base64 at line {i}");
-                            continue;
-                        };
-                        history.extend(Op::decode_history(base64, pad_char.parse().unwrap()));
+                    "ebver" => {
+                        let version: usize = parse(line.split(' ').nth(1).unwrap_or_default(), i)?;
+                        baseline = false;
+                        if version != VERSION {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "unsupported version {version} at line {i}"
+                            )));
+                        }
+                    }
+                    "ebh" => match line.split(' ').skip(1).collect::<Vec<_>>()[..] {
+                        [base64, pad_char] if !baseline => {
+                            history.extend(Op::decode_history(base64, parse(pad_char, i)?)?);
+                        }
+                        [base64, pad_char] => {
+                            let bits = Op::unpad(base64, parse(pad_char, i)?)?;
+                            history.extend(Op::decode_prefix(bits.iter().by_vals(), None, false)?);
+                        }
+                        [base64, count, name] if !baseline => {
+                            encoding = HistoryEncoding::from_name(name).ok_or_else(|| {
+                                EdgeBreakerError::MalformedInput(format!(
+                                    "unknown history encoding '{name}' at line {i}"
+                                ))
+                            })?;
+                            let bytes = BASE64_STANDARD_NO_PAD.decode(base64).map_err(|err| {
+                                EdgeBreakerError::CorruptHistory(format!("invalid base64: {err}"))
+                            })?;
+                            history.extend(encoding.decode(&bytes, parse(count, i)?)?);
+                        }
+                        _ => {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "expected history and padding at line {i}"
+                            )));
+                        }
+                    },
+                    "ebt" if !baseline => {
+                        let base64 = line.split(' ').nth(1).unwrap_or_default();
+                        table = Some(BASE64_STANDARD_NO_PAD.decode(base64).map_err(|err| {
+                            EdgeBreakerError::MalformedInput(format!("invalid base64: {err}"))
+                        })?);
                     }
                     "ebt" => {
                         let entries = line
@@ -90,19 +222,18 @@ impl Obj {
                             .collect::<Vec<_>>();
 
                         for entry in entries {
-                            let vals = entry.split('/').collect::<Vec<_>>();
-                            if vals.len() == 2 {
-                                table.push(Table::Hole(
-                                    vals[0].parse().unwrap(),
-                                    vals[1].parse().unwrap(),
-                                ));
-                            } else {
-                                table.push(Table::Merge(
-                                    vals[0].parse().unwrap(),
-                                    vals[1].parse().unwrap(),
-                                    vals[2].parse().unwrap(),
-                                    vals[3].parse().unwrap(),
-                                ));
+                            let vals = entry
+                                .split('/')
+                                .map(|w| parse(w, i))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            match vals[..] {
+                                [s, l] => baseline_table.push((s, Table::Hole(l))),
+                                [s, p, o, l] => baseline_table.push((s, Table::Merge(p, o, l))),
+                                _ => {
+                                    return Err(EdgeBreakerError::MalformedInput(format!(
+                                        "invalid table entry '{entry}' at line {i}"
+                                    )));
+                                }
                             }
                         }
                     }
@@ -116,17 +247,139 @@ impl Obj {
                         for entry in entries {
                             let [pos_word, idx_word] = entry.split('/').collect::<Vec<_>>()[..]
                             else {
-                                warn!(
-                                    "Failed to parse edge breaker duplicated at line {i}: {line}"
-                                );
-                                continue;
+                                return Err(EdgeBreakerError::MalformedInput(format!(
+                                    "invalid duplicate entry '{entry}' at line {i}"
+                                )));
                             };
 
-                            let pos = pos_word.parse().unwrap();
-                            let idx = idx_word.parse().unwrap();
+                            let pos = parse(pos_word, i)?;
+                            let idx = parse(idx_word, i)?;
                             dup.push((pos, idx));
                         }
                     }
+                    "ebf" => {
+                        for word in line.split(' ').skip(1).filter(|x| !x.is_empty()) {
+                            eb_flipped.push(parse(word, i)?);
+                        }
+                    }
+                    "ebw" => {
+                        for entry in line.split(' ').skip(1).filter(|x| !x.is_empty()) {
+                            let [corner_word, vertex_word] =
+                                entry.split('/').collect::<Vec<_>>()[..]
+                            else {
+                                return Err(EdgeBreakerError::MalformedInput(format!(
+                                    "invalid welded corner '{entry}' at line {i}"
+                                )));
+                            };
+                            eb_welded.push((parse(corner_word, i)?, parse(vertex_word, i)?));
+                        }
+                    }
+                    "ebc" => {
+                        for entry in line.split(' ').skip(1).filter(|x| !x.is_empty()) {
+                            let fields = entry
+                                .split('/')
+                                .map(|word| parse(word, i))
+                                .collect::<Result<Vec<usize>, _>>()?;
+                            let [
+                                faces,
+                                vertices,
+                                history_bit,
+                                table,
+                                dup,
+                                first,
+                                count,
+                                geometry,
+                                t0,
+                                t1,
+                                n0,
+                                n1,
+                                polygons,
+                            ] = fields[..]
+                            else {
+                                return Err(EdgeBreakerError::MalformedInput(format!(
+                                    "invalid component entry '{entry}' at line {i}"
+                                )));
+                            };
+                            eb_components.push(ComponentEntry {
+                                faces,
+                                vertices,
+                                history_bit,
+                                table,
+                                dup,
+                                vertex_range: first..first + count,
+                                geometry,
+                                texcoords: (t0, t1),
+                                normals: (n0, n1),
+                                polygons,
+                            });
+                        }
+                    }
+                    "ebn" => {
+                        let base64 = line.split(' ').nth(1).unwrap_or_default();
+                        eb_polygons =
+                            Some(BASE64_STANDARD_NO_PAD.decode(base64).map_err(|err| {
+                                EdgeBreakerError::MalformedInput(format!("invalid base64: {err}"))
+                            })?);
+                    }
+                    "ebvt" | "ebvn" => {
+                        let base64 = line.split(' ').nth(1).unwrap_or_default();
+                        let corners = BASE64_STANDARD_NO_PAD.decode(base64).map_err(|err| {
+                            EdgeBreakerError::MalformedInput(format!("invalid base64: {err}"))
+                        })?;
+                        if line.starts_with("ebvt") {
+                            eb_texcoords = Some(corners);
+                        } else {
+                            eb_normals = Some(corners);
+                        }
+                    }
+                    "ebp" => {
+                        let mut words = line.split(' ').skip(1).filter(|x| !x.is_empty());
+                        let (Some(kind), Some(name)) = (words.next(), words.next()) else {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "expected vertex property type and name at line {i}"
+                            )));
+                        };
+                        eb_properties.push(VertexProperty {
+                            name: name.to_string(),
+                            kind: ScalarKind::from_name(kind).ok_or_else(|| {
+                                EdgeBreakerError::MalformedInput(format!(
+                                    "unknown property type '{kind}' at line {i}"
+                                ))
+                            })?,
+                            values: words.map(|w| parse(w, i)).collect::<Result<_, _>>()?,
+                        });
+                    }
+                    "ebg" => {
+                        let words = line
+                            .split(' ')
+                            .skip(1)
+                            .filter(|x| !x.is_empty())
+                            .collect::<Vec<_>>();
+                        let [bits, ref bounds @ .., count, base64] = words[..] else {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "expected quantized geometry at line {i}"
+                            )));
+                        };
+                        let [x0, y0, z0, x1, y1, z1] = bounds
+                            .iter()
+                            .map(|w| parse(w, i))
+                            .collect::<Result<Vec<f32>, _>>()?[..]
+                        else {
+                            return Err(EdgeBreakerError::MalformedInput(format!(
+                                "expected 6 bounding box coordinates at line {i}"
+                            )));
+                        };
+                        let data = BASE64_STANDARD_NO_PAD.decode(base64).map_err(|err| {
+                            EdgeBreakerError::MalformedInput(format!("invalid base64: {err}"))
+                        })?;
+                        geometry = Some(QuantizedGeometry {
+                            bits: parse(bits, i)?,
+                            min: [x0, y0, z0],
+                            max: [x1, y1, z1],
+                            count: parse(count, i)?,
+                            data,
+                        });
+                    }
                     _ => warn!("Failed to parse line {i}: {line}"),
                 },
 
@@ -135,45 +388,422 @@ impl Obj {
             }
         }
 
-        Obj {
+        for (name, corners) in [
+            ("texture coordinates", &face_texcoords),
+            ("normals", &face_normals),
+        ] {
+            if !corners.is_empty() && corners.len() != faces.len() {
+                return Err(EdgeBreakerError::MalformedInput(format!(
+                    "only some faces have {name}"
+                )));
+            }
+        }
+
+        let (history, table) = match table {
+            _ if baseline => expand_baseline(history, &baseline_table)?,
+            Some(bytes) => {
+                let table = Table::decode(&bytes, &history)?;
+                (history, table)
+            }
+            None => (history, Vec::new()),
+        };
+
+        // Plain triangle meshes do not need the polygon sizes
+        if polygons.iter().all(|&n| n == 3) {
+            polygons.clear();
+        }
+
+        Ok(Obj {
             vertices,
             faces,
+            texcoords,
+            normals,
+            face_texcoords,
+            face_normals,
+            polygons,
             eb_history: history,
             eb_table: table,
             eb_dup: dup,
-        }
+            eb_encoding: encoding,
+            eb_geometry: geometry,
+            eb_texcoords,
+            eb_normals,
+            eb_properties,
+            eb_flipped,
+            eb_welded,
+            eb_polygons,
+            eb_components,
+        })
     }
 
-    pub fn write<T: Write>(&self, writer: &mut T) {
+    pub fn write<T: Write + ?Sized>(&self, writer: &mut T) -> io::Result<()> {
         for v in &self.vertices {
-            let _ = write!(writer, "v {} {} {}\n", v[0], v[1], v[2]);
+            writeln!(writer, "v {} {} {}", v[0], v[1], v[2])?;
+        }
+        for vt in &self.texcoords {
+            writeln!(writer, "vt {} {}", vt[0], vt[1])?;
+        }
+        for vn in &self.normals {
+            writeln!(writer, "vn {} {} {}", vn[0], vn[1], vn[2])?;
         }
-        for f in &self.faces {
-            let _ = write!(writer, "f {} {} {}\n", f[0], f[1], f[2]);
+        // Corners of a fan are the first two of its first face followed by
+        // the last one of every face
+        let mut sizes = self.polygons.iter().copied();
+        let mut i = 0;
+        while i < self.faces.len() {
+            let size = sizes.next().unwrap_or(3).min(self.faces.len() - i + 2);
+            writer.write_all(b"f")?;
+            let fan = (i..i + size - 2).map(|t| (t, 2));
+            for (t, k) in [(i, 0), (i, 1)].into_iter().chain(fan) {
+                let v = self.faces[t][k];
+                match (self.face_texcoords.get(t), self.face_normals.get(t)) {
+                    (None, None) => write!(writer, " {v}")?,
+                    (Some(t), None) => write!(writer, " {v}/{}", t[k])?,
+                    (None, Some(n)) => write!(writer, " {v}//{}", n[k])?,
+                    (Some(t), Some(n)) => write!(writer, " {v}/{}/{}", t[k], n[k])?,
+                }
+            }
+            writer.write_all(b"\n")?;
+            i += size - 2;
         }
 
         if !self.eb_history.is_empty() {
-            let (base64, pad) = Op::encode_history(&self.eb_history);
-            let _ = write!(writer, "ebh {} {}\n", base64, pad);
+            writeln!(writer, "ebver {VERSION}")?;
+            match self.eb_encoding {
+                HistoryEncoding::Prefix => {
+                    let (base64, pad) = Op::encode_history(&self.eb_history);
+                    writeln!(writer, "ebh {} {}", base64, pad)?;
+                }
+                encoding @ HistoryEncoding::Arithmetic => {
+                    // Components are coded on their own, one line each
+                    let (bytes, offsets) =
+                        encoding.encode_components(&self.eb_history, &self.eb_components);
+                    let counts = match self.eb_components.is_empty() {
+                        true => vec![self.eb_history.len()],
+                        false => self.eb_components.iter().map(|c| c.faces).collect(),
+                    };
+                    let mut ends = offsets.iter().skip(1).map(|bit| bit / 8);
+                    let mut start = 0;
+                    for count in counts {
+                        let end = ends.next().unwrap_or(bytes.len());
+                        let base64 = BASE64_STANDARD_NO_PAD.encode(&bytes[start..end]);
+                        writeln!(writer, "ebh {} {} {}", base64, count, encoding.name())?;
+                        start = end;
+                    }
+                }
+                encoding => {
+                    let base64 = BASE64_STANDARD_NO_PAD.encode(encoding.encode(&self.eb_history));
+                    let count = self.eb_history.len();
+                    writeln!(writer, "ebh {} {} {}", base64, count, encoding.name())?;
+                }
+            }
         }
 
         if !self.eb_table.is_empty() {
-            let _ = writer.write(b"ebt");
-            for entry in &self.eb_table {
-                let _ = match entry {
-                    Table::Hole(i1, i2) => write!(writer, " {i1}/{i2}"),
-                    Table::Merge(i1, i2, i3, i4) => write!(writer, " {i1}/{i2}/{i3}/{i4}"),
-                };
-            }
-            let _ = writer.write(b"\n");
+            let base64 = BASE64_STANDARD_NO_PAD.encode(Table::encode(&self.eb_table));
+            writeln!(writer, "ebt {base64}")?;
         }
 
         if !self.eb_dup.is_empty() {
-            let _ = writer.write(b"ebd");
+            writer.write_all(b"ebd")?;
             for (pos, idx) in &self.eb_dup {
-                let _ = write!(writer, " {pos}/{idx}");
+                write!(writer, " {pos}/{idx}")?;
             }
-            let _ = writer.write(b"\n");
+            writer.write_all(b"\n")?;
         }
+
+        if !self.eb_flipped.is_empty() {
+            writer.write_all(b"ebf")?;
+            for f in &self.eb_flipped {
+                write!(writer, " {f}")?;
+            }
+            writer.write_all(b"\n")?;
+        }
+
+        if !self.eb_welded.is_empty() {
+            writer.write_all(b"ebw")?;
+            for (corner, vertex) in &self.eb_welded {
+                write!(writer, " {corner}/{vertex}")?;
+            }
+            writer.write_all(b"\n")?;
+        }
+
+        if !self.eb_components.is_empty() {
+            writer.write_all(b"ebc")?;
+            for c in &self.eb_components {
+                write!(
+                    writer,
+                    " {}/{}/{}/{}/{}/{}/{}/{}/{}/{}/{}/{}/{}",
+                    c.faces,
+                    c.vertices,
+                    c.history_bit,
+                    c.table,
+                    c.dup,
+                    c.vertex_range.start,
+                    c.vertex_range.len(),
+                    c.geometry,
+                    c.texcoords.0,
+                    c.texcoords.1,
+                    c.normals.0,
+                    c.normals.1,
+                    c.polygons
+                )?;
+            }
+            writer.write_all(b"\n")?;
+        }
+
+        if let Some(polygons) = &self.eb_polygons {
+            writeln!(writer, "ebn {}", BASE64_STANDARD_NO_PAD.encode(polygons))?;
+        }
+
+        if let Some(corners) = &self.eb_texcoords {
+            writeln!(writer, "ebvt {}", BASE64_STANDARD_NO_PAD.encode(corners))?;
+        }
+        if let Some(corners) = &self.eb_normals {
+            writeln!(writer, "ebvn {}", BASE64_STANDARD_NO_PAD.encode(corners))?;
+        }
+
+        for p in &self.eb_properties {
+            write!(writer, "ebp {} {}", p.kind.name(), p.name)?;
+            for v in &p.values {
+                write!(writer, " {v}")?;
+            }
+            writer.write_all(b"\n")?;
+        }
+
+        if let Some(g) = &self.eb_geometry {
+            let [x0, y0, z0] = g.min;
+            let [x1, y1, z1] = g.max;
+            writeln!(
+                writer,
+                "ebg {} {x0} {y0} {z0} {x1} {y1} {z1} {} {}",
+                g.bits,
+                g.count,
+                BASE64_STANDARD_NO_PAD.encode(&g.data)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Decompress `compressed` straight to OBJ text. Faces are written while
+    /// they are decoded instead of being collected first, except when the
+    /// quantized geometry, the attributes or the polygons need all of them.
+    pub fn write_decompressed<T: Write + ?Sized>(
+        compressed: &Compressed,
+        options: &DecompressOptions,
+        writer: &mut T,
+    ) -> Result<(), EdgeBreakerError> {
+        if compressed.geometry.is_some()
+            || compressed.texcoords.is_some()
+            || compressed.normals.is_some()
+            || compressed.polygons.is_some()
+            || (options.threads > 1 && !compressed.components.is_empty())
+        {
+            let mesh = decompress_with(compressed, options)?;
+            return Ok(Obj::from(mesh).write(writer)?);
+        }
+
+        for p in &compressed.properties {
+            warn!("OBJ output does not keep vertex property '{}'", p.name);
+        }
+        for v in &compressed.vertices {
+            writeln!(writer, "v {} {} {}", v[0], v[1], v[2])?;
+        }
+        decompress_faces(compressed, options, |f| {
+            writeln!(writer, "f {} {} {}", f[0] + 1, f[1] + 1, f[2] + 1)?;
+            Ok(())
+        })
+    }
+}
+
+// Files without an `ebver` line have a history without a header, in which S,
+// H and M share a code, and list the table in `ebt` as decimal entries that
+// count the S codes since the entry before. Tell the three apart again.
+fn expand_baseline(
+    ops: Vec<Op>,
+    table: &[(usize, Table)],
+) -> Result<(Vec<Op>, Vec<Table>), EdgeBreakerError> {
+    let mut history = Vec::with_capacity(ops.len());
+    let (mut t, mut s) = (0, 0);
+    for op in ops {
+        match table.get(t) {
+            Some(&(count, entry)) if op == Op::S && s == count => {
+                history.push(match entry {
+                    Table::Hole(_) => Op::H,
+                    Table::Merge(..) => Op::M,
+                });
+                (t, s) = (t + 1, 0);
+            }
+            _ => {
+                s += (op == Op::S) as usize;
+                history.push(op);
+            }
+        }
+    }
+
+    if t < table.len() {
+        return Err(EdgeBreakerError::TableMismatch(format!(
+            "{} table entries are not referenced by the history",
+            table.len() - t
+        )));
+    }
+    Ok((history, table.iter().map(|e| e.1).collect()))
+}
+
+impl From<Obj> for Mesh {
+    fn from(obj: Obj) -> Self {
+        let zero_based = |faces: Vec<[usize; 3]>| {
+            faces
+                .into_iter()
+                .map(|f| f.map(|v| v.wrapping_sub(1)))
+                .collect()
+        };
+        Mesh {
+            vertices: obj.vertices,
+            faces: zero_based(obj.faces),
+            texcoords: obj.texcoords,
+            normals: obj.normals,
+            face_texcoords: zero_based(obj.face_texcoords),
+            face_normals: zero_based(obj.face_normals),
+            polygons: obj.polygons,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Mesh> for Obj {
+    fn from(mesh: Mesh) -> Self {
+        for p in &mesh.properties {
+            warn!("OBJ output does not keep vertex property '{}'", p.name);
+        }
+        let one_based =
+            |faces: Vec<[usize; 3]>| faces.into_iter().map(|f| f.map(|v| v + 1)).collect();
+        Obj {
+            vertices: mesh.vertices,
+            faces: one_based(mesh.faces),
+            texcoords: mesh.texcoords,
+            normals: mesh.normals,
+            face_texcoords: one_based(mesh.face_texcoords),
+            face_normals: one_based(mesh.face_normals),
+            polygons: mesh.polygons,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Obj> for Compressed {
+    fn from(obj: Obj) -> Self {
+        Compressed {
+            vertices: obj.vertices,
+            history: obj.eb_history,
+            table: obj.eb_table,
+            dup: obj.eb_dup,
+            encoding: obj.eb_encoding,
+            geometry: obj.eb_geometry,
+            texcoords: obj.eb_texcoords.map(|corners| Attribute {
+                values: obj.texcoords,
+                corners,
+            }),
+            normals: obj.eb_normals.map(|corners| Attribute {
+                values: obj.normals,
+                corners,
+            }),
+            properties: obj.eb_properties,
+            flipped: obj.eb_flipped,
+            welded: obj.eb_welded,
+            polygons: obj.eb_polygons,
+            components: obj.eb_components,
+        }
+    }
+}
+
+impl From<Compressed> for Obj {
+    fn from(compressed: Compressed) -> Self {
+        let mut obj = Obj {
+            vertices: compressed.vertices,
+            eb_history: compressed.history,
+            eb_table: compressed.table,
+            eb_dup: compressed.dup,
+            eb_encoding: compressed.encoding,
+            eb_geometry: compressed.geometry,
+            eb_properties: compressed.properties,
+            eb_flipped: compressed.flipped,
+            eb_welded: compressed.welded,
+            eb_polygons: compressed.polygons,
+            eb_components: compressed.components,
+            ..Default::default()
+        };
+        if let Some(texcoords) = compressed.texcoords {
+            obj.texcoords = texcoords.values;
+            obj.eb_texcoords = Some(texcoords.corners);
+        }
+        if let Some(normals) = compressed.normals {
+            obj.normals = normals.values;
+            obj.eb_normals = Some(normals.corners);
+        }
+        obj
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compress, decompress};
+
+    #[test]
+    fn baseline_files_are_read() {
+        // 3x3 grid of quads with the middle one missing
+        let mut mesh = Mesh::default();
+        for i in 0..16 {
+            mesh.vertices.push([(i % 4) as f32, (i / 4) as f32, 0.0]);
+        }
+        for j in 0..3 {
+            for i in (0..3).filter(|&i| (i, j) != (1, 1)) {
+                let a = j * 4 + i;
+                mesh.faces.extend([[a, a + 1, a + 5], [a, a + 5, a + 4]]);
+            }
+        }
+        let compressed = compress(&mesh).unwrap();
+        assert!(compressed.history.contains(&Op::H));
+
+        // No header bit, H and M coded as S, and S counts in the table
+        let mut text = String::new();
+        for v in &compressed.vertices {
+            text += &format!("v {} {} {}\n", v[0], v[1], v[2]);
+        }
+        let shared = compressed.history.iter().map(|&op| match op {
+            Op::H | Op::M => Op::S,
+            op => op,
+        });
+        let (base64, pad) = Op::encode_history(&shared.collect::<Vec<_>>());
+        let mut bits = Op::unpad(&base64, pad).unwrap();
+        bits.remove(0);
+        let pad = (8 - bits.len() % 8) % 8;
+        let base64 = BASE64_STANDARD_NO_PAD.encode(bits.into_vec());
+        text += &format!("ebh {base64} {pad}\nebt");
+        let mut entries = compressed.table.iter().peekable();
+        let mut s = 0;
+        for op in &compressed.history {
+            match (op, entries.next_if(|_| matches!(op, Op::H | Op::M))) {
+                (_, Some(Table::Hole(l))) => text += &format!(" {s}/{l}"),
+                (_, Some(Table::Merge(p, o, l))) => text += &format!(" {s}/{p}/{o}/{l}"),
+                (Op::S, None) => s += 1,
+                _ => continue,
+            }
+            if !matches!(op, Op::S) {
+                s = 0;
+            }
+        }
+        text += "\nebd";
+        for (pos, idx) in &compressed.dup {
+            text += &format!(" {pos}/{idx}");
+        }
+        text += "\n";
+
+        let obj = Obj::read(&mut text.as_bytes()).unwrap();
+        assert_eq!(obj.eb_history, compressed.history);
+        let decoded = decompress(&Compressed::from(obj)).unwrap();
+        assert_eq!(decoded, decompress(&compressed).unwrap());
     }
 }
@@ -0,0 +1,445 @@
+// ,---------------------------------------------------------------------------
+// | PLY: ASCII and binary little endian meshes
+// '---------------------------------------------------------------------------
+
+use log::warn;
+use std::{
+    io::{self, BufRead, Write},
+    str::SplitWhitespace,
+};
+
+use crate::{
+    EdgeBreakerError,
+    mesh::{Mesh, ScalarKind, VertexProperty},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlyFormat {
+    Ascii,
+    #[default]
+    BinaryLittleEndian,
+}
+
+/// Vertex positions, faces and any further scalar vertex properties of a PLY
+/// file. Faces use zero-based indices, polygons are fan triangulated and
+/// their sizes kept like in [`Mesh::polygons`].
+#[derive(Debug, Default)]
+pub struct Ply {
+    pub vertices: Vec<[f32; 3]>,
+    pub faces: Vec<[usize; 3]>,
+    pub properties: Vec<VertexProperty>,
+    pub polygons: Vec<usize>,
+}
+
+enum PropertyType {
+    Scalar(ScalarKind),
+    List(ScalarKind, ScalarKind),
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, PropertyType)>,
+}
+
+// Remaining data after the header
+enum Body<'a> {
+    Ascii(SplitWhitespace<'a>),
+    Binary(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn read(&mut self, kind: ScalarKind) -> Result<f64, EdgeBreakerError> {
+        let truncated = || EdgeBreakerError::MalformedInput("PLY body is truncated".to_string());
+        match self {
+            Body::Ascii(words) => {
+                let word = words.next().ok_or_else(truncated)?;
+                word.parse().map_err(|_| {
+                    EdgeBreakerError::MalformedInput(format!("invalid PLY value '{word}'"))
+                })
+            }
+            Body::Binary(bytes) => {
+                if bytes.len() < kind.size() {
+                    return Err(truncated());
+                }
+                let (value, rest) = bytes.split_at(kind.size());
+                *bytes = rest;
+                Ok(kind.from_le_bytes(value))
+            }
+        }
+    }
+
+    // List sizes and vertex indices, whatever type the header gives them
+    fn read_count(&mut self, kind: ScalarKind) -> Result<usize, EdgeBreakerError> {
+        let value = self.read(kind)?;
+        // usize::MAX as f64 rounds up to 2^64, which no usize holds
+        if value.is_finite() && value >= 0.0 && value.fract() == 0.0 && value < usize::MAX as f64 {
+            Ok(value as usize)
+        } else {
+            Err(EdgeBreakerError::MalformedInput(format!(
+                "PLY count or index {value} is not a non-negative integer"
+            )))
+        }
+    }
+}
+
+impl Ply {
+    pub fn read<T: BufRead + ?Sized>(reader: &mut T) -> Result<Self, EdgeBreakerError> {
+        let malformed = |msg: String| EdgeBreakerError::MalformedInput(msg);
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(malformed("PLY header is not terminated".to_string()));
+            }
+            let line = line.trim_end().to_string();
+            if line == "end_header" {
+                break;
+            }
+            lines.push(line);
+        }
+
+        if lines.first().map(String::as_str) != Some("ply") {
+            return Err(malformed("missing PLY magic".to_string()));
+        }
+
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        for (i, line) in lines.iter().enumerate().skip(1) {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let kind = |name: &str| {
+                ScalarKind::from_name(name)
+                    .ok_or_else(|| malformed(format!("unknown PLY type '{name}' at line {i}")))
+            };
+            match words[..] {
+                ["format", "ascii", _] => format = Some(PlyFormat::Ascii),
+                ["format", "binary_little_endian", _] => {
+                    format = Some(PlyFormat::BinaryLittleEndian)
+                }
+                ["format", other, _] => {
+                    return Err(malformed(format!("unsupported PLY format '{other}'")));
+                }
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| {
+                        malformed(format!("invalid element count '{count}' at line {i}"))
+                    })?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count, item, name] => {
+                    let element = elements.last_mut().ok_or_else(|| {
+                        malformed(format!("property outside of an element at line {i}"))
+                    })?;
+                    let ty = PropertyType::List(kind(count)?, kind(item)?);
+                    element.properties.push((name.to_string(), ty));
+                }
+                ["property", ty, name] => {
+                    let element = elements.last_mut().ok_or_else(|| {
+                        malformed(format!("property outside of an element at line {i}"))
+                    })?;
+                    element
+                        .properties
+                        .push((name.to_string(), PropertyType::Scalar(kind(ty)?)));
+                }
+                ["comment", ..] | ["obj_info", ..] | [] => {}
+                _ => warn!("Failed to parse PLY header line {i}: {line}"),
+            }
+        }
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let text;
+        let mut body = match format {
+            Some(PlyFormat::Ascii) => {
+                text = String::from_utf8(data)
+                    .map_err(|_| malformed("ASCII PLY body is not valid UTF-8".to_string()))?;
+                Body::Ascii(text.split_whitespace())
+            }
+            Some(PlyFormat::BinaryLittleEndian) => Body::Binary(&data),
+            None => return Err(malformed("PLY header has no format".to_string())),
+        };
+
+        let mut ply = Ply::default();
+        for element in &elements {
+            match element.name.as_str() {
+                "vertex" => ply.read_vertices(element, &mut body)?,
+                "face" => ply.read_faces(element, &mut body)?,
+                name => {
+                    warn!("Skipping PLY element '{name}'");
+                    for _ in 0..element.count {
+                        for (_, ty) in &element.properties {
+                            skip(ty, &mut body)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ply)
+    }
+
+    fn read_vertices(
+        &mut self,
+        element: &Element,
+        body: &mut Body,
+    ) -> Result<(), EdgeBreakerError> {
+        let axis = |name: &str| element.properties.iter().position(|(n, _)| n == name);
+        let (Some(x), Some(y), Some(z)) = (axis("x"), axis("y"), axis("z")) else {
+            return Err(EdgeBreakerError::MalformedInput(
+                "PLY vertices have no x, y and z properties".to_string(),
+            ));
+        };
+
+        let mut extra = Vec::new();
+        for (i, (name, ty)) in element.properties.iter().enumerate() {
+            match ty {
+                PropertyType::Scalar(kind) if ![x, y, z].contains(&i) => {
+                    extra.push((i, self.properties.len()));
+                    self.properties.push(VertexProperty {
+                        name: name.clone(),
+                        kind: *kind,
+                        values: Vec::new(),
+                    });
+                }
+                PropertyType::List(..) => warn!("Skipping vertex list property '{name}'"),
+                PropertyType::Scalar(_) => {}
+            }
+        }
+
+        let mut row = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            for (value, (_, ty)) in row.iter_mut().zip(&element.properties) {
+                *value = match ty {
+                    PropertyType::Scalar(kind) => body.read(*kind)?,
+                    PropertyType::List(..) => skip(ty, body).map(|()| 0.0)?,
+                };
+            }
+            self.vertices
+                .push([row[x] as f32, row[y] as f32, row[z] as f32]);
+            for &(i, p) in &extra {
+                self.properties[p].values.push(row[i]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_faces(&mut self, element: &Element, body: &mut Body) -> Result<(), EdgeBreakerError> {
+        let indices = element
+            .properties
+            .iter()
+            .position(|(n, ty)| {
+                matches!(ty, PropertyType::List(..))
+                    && (n == "vertex_indices" || n == "vertex_index")
+            })
+            .ok_or_else(|| {
+                EdgeBreakerError::MalformedInput("PLY faces have no vertex indices".to_string())
+            })?;
+
+        for _ in 0..element.count {
+            for (i, (_, ty)) in element.properties.iter().enumerate() {
+                match ty {
+                    PropertyType::List(count, item) if i == indices => {
+                        let n = body.read_count(*count)?;
+                        let vals = (0..n)
+                            .map(|_| body.read_count(*item))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if n < 3 {
+                            return Err(EdgeBreakerError::MalformedInput(
+                                "PLY face has fewer than 3 vertices".to_string(),
+                            ));
+                        }
+                        for i in 1..=n - 2 {
+                            self.faces.push([vals[0], vals[i], vals[i + 1]]);
+                        }
+                        self.polygons.push(n);
+                    }
+                    _ => skip(ty, body)?,
+                }
+            }
+        }
+
+        // Plain triangle meshes do not need the polygon sizes
+        if self.polygons.iter().all(|&n| n == 3) {
+            self.polygons.clear();
+        }
+
+        Ok(())
+    }
+
+    // Corners of every polygon, the first two of the fan's first face
+    // followed by the last one of every face
+    fn outlines(&self) -> Vec<Vec<usize>> {
+        let mut sizes = self.polygons.iter().copied();
+        let mut outlines = Vec::new();
+        let mut i = 0;
+        while i < self.faces.len() {
+            let size = sizes.next().unwrap_or(3).min(self.faces.len() - i + 2);
+            let fan = &self.faces[i..i + size - 2];
+            let mut outline = vec![fan[0][0], fan[0][1]];
+            outline.extend(fan.iter().map(|f| f[2]));
+            outlines.push(outline);
+            i += size - 2;
+        }
+        outlines
+    }
+
+    pub fn write<T: Write + ?Sized>(&self, writer: &mut T, format: PlyFormat) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        match format {
+            PlyFormat::Ascii => writeln!(writer, "format ascii 1.0")?,
+            PlyFormat::BinaryLittleEndian => writeln!(writer, "format binary_little_endian 1.0")?,
+        }
+        writeln!(writer, "element vertex {}", self.vertices.len())?;
+        for axis in ["x", "y", "z"] {
+            writeln!(writer, "property float {axis}")?;
+        }
+        for p in &self.properties {
+            writeln!(writer, "property {} {}", p.kind.name(), p.name)?;
+        }
+        let outlines = self.outlines();
+        writeln!(writer, "element face {}", outlines.len())?;
+        let count = match outlines.iter().any(|o| o.len() > u8::MAX as usize) {
+            true => ScalarKind::U32,
+            false => ScalarKind::U8,
+        };
+        writeln!(writer, "property list {} int vertex_indices", count.name())?;
+        writeln!(writer, "end_header")?;
+
+        match format {
+            PlyFormat::Ascii => {
+                for (i, v) in self.vertices.iter().enumerate() {
+                    write!(writer, "{} {} {}", v[0], v[1], v[2])?;
+                    for p in &self.properties {
+                        match p.kind {
+                            ScalarKind::F32 => write!(writer, " {}", p.values[i] as f32)?,
+                            ScalarKind::F64 => write!(writer, " {}", p.values[i])?,
+                            _ => write!(writer, " {}", p.values[i] as i64)?,
+                        }
+                    }
+                    writer.write_all(b"\n")?;
+                }
+                for outline in &outlines {
+                    write!(writer, "{}", outline.len())?;
+                    for v in outline {
+                        write!(writer, " {v}")?;
+                    }
+                    writer.write_all(b"\n")?;
+                }
+            }
+            PlyFormat::BinaryLittleEndian => {
+                for (i, v) in self.vertices.iter().enumerate() {
+                    for c in v {
+                        writer.write_all(&c.to_le_bytes())?;
+                    }
+                    for p in &self.properties {
+                        writer.write_all(&p.kind.to_le_bytes(p.values[i]))?;
+                    }
+                }
+                for outline in &outlines {
+                    writer.write_all(&count.to_le_bytes(outline.len() as f64))?;
+                    for &v in outline {
+                        writer.write_all(&(v as i32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn skip(ty: &PropertyType, body: &mut Body) -> Result<(), EdgeBreakerError> {
+    match ty {
+        PropertyType::Scalar(kind) => body.read(*kind).map(|_| ()),
+        PropertyType::List(count, item) => {
+            for _ in 0..body.read_count(*count)? {
+                body.read(*item)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl From<Ply> for Mesh {
+    fn from(ply: Ply) -> Self {
+        Mesh {
+            vertices: ply.vertices,
+            faces: ply.faces,
+            properties: ply.properties,
+            polygons: ply.polygons,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Mesh> for Ply {
+    fn from(mesh: Mesh) -> Self {
+        if !mesh.face_texcoords.is_empty() || !mesh.face_normals.is_empty() {
+            warn!("PLY output does not keep per-corner texture coordinates and normals");
+        }
+        Ply {
+            vertices: mesh.vertices,
+            faces: mesh.faces,
+            properties: mesh.properties,
+            polygons: mesh.polygons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ply_roundtrip() {
+        let ply = Ply {
+            vertices: vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.5, 0.0],
+                [1.0, 1.0, 0.25],
+            ],
+            faces: vec![[0, 1, 2], [2, 1, 3], [2, 3, 0]],
+            polygons: vec![3, 4],
+            properties: vec![VertexProperty {
+                name: "red".to_string(),
+                kind: ScalarKind::U8,
+                values: vec![0.0, 64.0, 128.0, 255.0],
+            }],
+        };
+
+        for format in [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian] {
+            let mut bytes = Vec::new();
+            ply.write(&mut bytes, format).unwrap();
+            let read = Ply::read(&mut &bytes[..]).unwrap();
+            assert_eq!(read.vertices, ply.vertices);
+            assert_eq!(read.faces, ply.faces);
+            assert_eq!(read.polygons, ply.polygons);
+            assert_eq!(read.properties, ply.properties);
+        }
+    }
+
+    #[test]
+    fn invalid_indices_are_rejected() {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+                      property float y\nproperty float z\nelement face 1\n\
+                      property list float float vertex_indices\nend_header\n\
+                      0 0 0\n1 0 0\n0 1 0\n";
+        let read = |face: &str| Ply::read(&mut format!("{header}{face}\n").as_bytes());
+        assert_eq!(read("3 0 1 2").unwrap().faces, [[0, 1, 2]]);
+        for face in [
+            "3 0 1 -2",
+            "3 0 1 1.5",
+            "3 0 1 nan",
+            "3 0 1 inf",
+            "3 0 1 1e30",
+            "-3 0 1 2",
+        ] {
+            assert!(
+                matches!(read(face), Err(EdgeBreakerError::MalformedInput(_))),
+                "{face}"
+            );
+        }
+    }
+}
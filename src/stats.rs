@@ -0,0 +1,186 @@
+// ,---------------------------------------------------------------------------
+// | Stats: what the compressor did with a mesh
+// '---------------------------------------------------------------------------
+
+use std::fmt;
+
+use crate::{
+    CompressOptions, Compressed, EdgeBreakerError, Mesh, Op, Table, edgebreaker, obj::Obj,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Number of each operation in the history, in CLERSMH order
+    pub ops: [(Op, usize); 7],
+    /// Size of the encoded history in bytes
+    pub history_bytes: usize,
+    pub table_entries: usize,
+    /// Size of the coded hole and merge table in bytes
+    pub table_bytes: usize,
+    pub dup_entries: usize,
+    pub components: usize,
+    pub holes: usize,
+    /// Half-edges on non-manifold edges
+    pub conflicts: usize,
+    /// Faces whose winding is restored after decoding
+    pub flipped: usize,
+    /// Vertices no face references
+    pub isolated: usize,
+    /// Input vertices welded into another one
+    pub welded: usize,
+    /// Size of the diagonal bits that restore the polygons in bytes
+    pub polygon_bytes: usize,
+    /// Size of the text output in bytes
+    pub text_size: usize,
+    /// Size of the binary container in bytes
+    pub binary_size: usize,
+    /// Size of the uncompressed input in bytes, if known
+    pub input_size: Option<usize>,
+}
+
+impl Stats {
+    pub fn bits_per_triangle(&self) -> f64 {
+        if self.triangles == 0 {
+            return 0.0;
+        }
+        (self.history_bytes * 8) as f64 / self.triangles as f64
+    }
+}
+
+// Components end with an E that does not close a split
+fn count_components(history: &[Op]) -> usize {
+    let mut open = 0;
+    let mut components = 0;
+    for op in history {
+        match op {
+            Op::S => open += 1,
+            Op::M => open -= 1,
+            Op::E if open == 0 => components += 1,
+            Op::E => open -= 1,
+            _ => {}
+        }
+    }
+    components
+}
+
+/// Compress `mesh` like [`crate::compress_with`] and report on the result.
+pub fn compress_with_stats(
+    mesh: &Mesh,
+    options: &CompressOptions,
+) -> Result<(Compressed, Stats), EdgeBreakerError> {
+    let encoded = edgebreaker::encode_mesh(mesh, options)?;
+    let compressed = encoded.compressed;
+
+    let ops = [Op::C, Op::L, Op::E, Op::R, Op::S, Op::M, Op::H]
+        .map(|op| (op, compressed.history.iter().filter(|&&o| o == op).count()));
+
+    let mut text = Vec::new();
+    Obj::from(compressed.clone()).write(&mut text)?;
+    let mut binary = Vec::new();
+    compressed.write_binary(&mut binary)?;
+
+    let stats = Stats {
+        vertices: compressed.vertex_count(),
+        triangles: compressed.history.len(),
+        ops,
+        history_bytes: compressed.encoding.encode(&compressed.history).len(),
+        table_entries: compressed.table.len(),
+        table_bytes: Table::encode(&compressed.table).len(),
+        dup_entries: compressed.dup.len(),
+        components: count_components(&compressed.history),
+        holes: ops[6].1,
+        conflicts: encoded.conflicts,
+        flipped: compressed.flipped.len(),
+        isolated: encoded.isolated,
+        welded: encoded
+            .welds
+            .iter()
+            .enumerate()
+            .filter(|&(p, &w)| p != w)
+            .count(),
+        polygon_bytes: compressed.polygons.as_ref().map_or(0, Vec::len),
+        text_size: text.len(),
+        binary_size: binary.len(),
+        input_size: None,
+    };
+
+    Ok((compressed, stats))
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "vertices: {}", self.vertices)?;
+        writeln!(f, "triangles: {}", self.triangles)?;
+        write!(f, "ops:")?;
+        for (op, count) in &self.ops {
+            write!(f, " {op:?}={count}")?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "history: {} bytes, {:.3} bits/triangle",
+            self.history_bytes,
+            self.bits_per_triangle()
+        )?;
+        writeln!(
+            f,
+            "table entries: {}, {} bytes",
+            self.table_entries, self.table_bytes
+        )?;
+        writeln!(f, "dup entries: {}", self.dup_entries)?;
+        writeln!(f, "components: {}", self.components)?;
+        writeln!(f, "holes: {}", self.holes)?;
+        writeln!(f, "non-manifold conflicts: {}", self.conflicts)?;
+        writeln!(f, "flipped faces: {}", self.flipped)?;
+        writeln!(f, "isolated vertices: {}", self.isolated)?;
+        writeln!(f, "welded vertices: {}", self.welded)?;
+        writeln!(f, "polygon bits: {} bytes", self.polygon_bytes)?;
+
+        let ratio = |size: usize| match self.input_size {
+            Some(input) if input > 0 => {
+                format!(" ({:.1}% of input)", 100.0 * size as f64 / input as f64)
+            }
+            _ => String::new(),
+        };
+        if let Some(input) = self.input_size {
+            writeln!(f, "input size: {input} bytes")?;
+        }
+        writeln!(
+            f,
+            "text size: {} bytes{}",
+            self.text_size,
+            ratio(self.text_size)
+        )?;
+        write!(
+            f,
+            "binary size: {} bytes{}",
+            self.binary_size,
+            ratio(self.binary_size)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_strip() {
+        // Strip of three triangles
+        let mesh = Mesh {
+            vertices: vec![[0.0; 3]; 5],
+            faces: vec![[0, 1, 2], [2, 1, 3], [2, 3, 4]],
+            ..Default::default()
+        };
+
+        let (compressed, stats) = compress_with_stats(&mesh, &CompressOptions::default()).unwrap();
+        assert_eq!(stats.triangles, 3);
+        assert_eq!(stats.ops.iter().map(|(_, n)| n).sum::<usize>(), 3);
+        assert_eq!(stats.components, 1);
+        assert_eq!(stats.holes, 0);
+        assert_eq!(stats.conflicts, 0);
+        assert_eq!(stats.dup_entries, compressed.dup.len());
+    }
+}
@@ -0,0 +1,198 @@
+// ,---------------------------------------------------------------------------
+// | STL: ASCII and binary triangle soups
+// '---------------------------------------------------------------------------
+
+use log::warn;
+use std::io::{self, BufRead, Write};
+
+use crate::{EdgeBreakerError, mesh::Mesh, weld::weld};
+
+/// Triangles of an STL file. STL does not share vertices between triangles,
+/// use [`Stl::to_mesh`] to weld them into an indexed mesh.
+#[derive(Debug, Default)]
+pub struct Stl {
+    pub triangles: Vec<[[f32; 3]; 3]>,
+}
+
+impl Stl {
+    pub fn read<T: BufRead + ?Sized>(reader: &mut T) -> Result<Self, EdgeBreakerError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        // Binary files may start with "solid" as well, so check the size first
+        if data.len() >= 84 {
+            let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+            if data.len() == 84 + 50 * count {
+                return Ok(Self::read_binary(&data[84..], count));
+            }
+        }
+
+        if data.starts_with(b"solid") {
+            let text = String::from_utf8(data).map_err(|_| {
+                EdgeBreakerError::MalformedInput("ASCII STL is not valid UTF-8".to_string())
+            })?;
+            return Self::read_ascii(&text);
+        }
+
+        Err(EdgeBreakerError::MalformedInput(
+            "input is neither ASCII nor binary STL".to_string(),
+        ))
+    }
+
+    fn read_binary(data: &[u8], count: usize) -> Self {
+        let triangles = data
+            .chunks_exact(50)
+            .take(count)
+            .map(|facet| {
+                // Skip the normal, the attribute byte count follows the corners
+                std::array::from_fn(|k| {
+                    std::array::from_fn(|i| {
+                        let at = 12 + 12 * k + 4 * i;
+                        f32::from_le_bytes(facet[at..at + 4].try_into().unwrap())
+                    })
+                })
+            })
+            .collect();
+        Stl { triangles }
+    }
+
+    fn read_ascii(text: &str) -> Result<Self, EdgeBreakerError> {
+        let mut triangles = Vec::new();
+        let mut corners = Vec::with_capacity(3);
+
+        for (i, line) in text.lines().enumerate() {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            match words[..] {
+                ["vertex", x, y, z] => {
+                    let mut v = [0.0; 3];
+                    for (c, word) in v.iter_mut().zip([x, y, z]) {
+                        *c = word.parse().map_err(|_| {
+                            EdgeBreakerError::MalformedInput(format!(
+                                "invalid value '{word}' at line {i}"
+                            ))
+                        })?;
+                    }
+                    corners.push(v);
+                }
+                ["endloop"] => {
+                    let triangle = corners.as_slice().try_into().map_err(|_| {
+                        EdgeBreakerError::MalformedInput(format!(
+                            "facet ending at line {i} does not have 3 vertices"
+                        ))
+                    })?;
+                    triangles.push(triangle);
+                    corners.clear();
+                }
+                ["solid", ..]
+                | ["endsolid", ..]
+                | ["facet", ..]
+                | ["endfacet"]
+                | ["outer", "loop"]
+                | [] => {}
+                _ => warn!("Failed to parse line {i}: {line}"),
+            }
+        }
+
+        Ok(Stl { triangles })
+    }
+
+    /// Binary STL with facet normals computed from the triangles.
+    pub fn write<T: Write + ?Sized>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&[0; 80])?;
+        writer.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+        for [a, b, c] in &self.triangles {
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [
+                u[1] * v[2] - u[2] * v[1],
+                u[2] * v[0] - u[0] * v[2],
+                u[0] * v[1] - u[1] * v[0],
+            ];
+            let len = n.iter().map(|c| c * c).sum::<f32>().sqrt();
+            let n = if len > 0.0 { n.map(|c| c / len) } else { n };
+            for c in n.iter().chain(a).chain(b).chain(c) {
+                writer.write_all(&c.to_le_bytes())?;
+            }
+            writer.write_all(&[0; 2])?;
+        }
+        Ok(())
+    }
+
+    /// Weld corners closer than `tolerance` (bitwise equal ones for zero)
+    /// into shared vertices. Triangles that collapse are dropped.
+    pub fn to_mesh(&self, tolerance: f32) -> Mesh {
+        let corners = self.triangles.iter().flatten().copied().collect::<Vec<_>>();
+        let (vertices, remap) = weld(&corners, tolerance);
+
+        let faces = remap
+            .chunks_exact(3)
+            .map(|f| [f[0], f[1], f[2]])
+            .filter(|f| f[0] != f[1] && f[1] != f[2] && f[2] != f[0])
+            .collect::<Vec<_>>();
+        if faces.len() < self.triangles.len() {
+            warn!(
+                "Dropped {} triangles that collapsed while welding",
+                self.triangles.len() - faces.len()
+            );
+        }
+
+        Mesh {
+            vertices,
+            faces,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Stl> for Mesh {
+    fn from(stl: Stl) -> Self {
+        stl.to_mesh(0.0)
+    }
+}
+
+impl From<Mesh> for Stl {
+    fn from(mesh: Mesh) -> Self {
+        Stl {
+            triangles: mesh
+                .faces
+                .iter()
+                .map(|f| f.map(|v| mesh.vertices[v]))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stl_welds_shared_corners() {
+        let ascii = "solid quad
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid quad
+";
+        let stl = Stl::read(&mut ascii.as_bytes()).unwrap();
+        let mut binary = Vec::new();
+        stl.write(&mut binary).unwrap();
+        let read = Stl::read(&mut &binary[..]).unwrap();
+        assert_eq!(read.triangles, stl.triangles);
+
+        let mesh = Mesh::from(read);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+}
@@ -0,0 +1,166 @@
+// ,---------------------------------------------------------------------------
+// | Verify: round-trip a mesh and compare the result
+// '---------------------------------------------------------------------------
+
+use std::{collections::HashMap, fmt};
+
+use crate::{CompressOptions, EdgeBreakerError, Mesh, decompress, edgebreaker, weld};
+
+/// Differences between a mesh and its compressed and decompressed version.
+/// Faces are given with the vertex indices of the input mesh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Verification {
+    pub input_faces: usize,
+    pub decoded_faces: usize,
+    /// Decoded faces that do not exist in the input in any orientation
+    pub mismatched: Vec<[usize; 3]>,
+    /// Decoded faces that exist in the input with the opposite winding
+    pub flipped: Vec<[usize; 3]>,
+    /// Input faces missing from the decoded mesh
+    pub lost: Vec<[usize; 3]>,
+    /// Decoded faces that appear more often than in the input
+    pub duplicated: Vec<[usize; 3]>,
+    /// Input vertices whose decoded position differs. Not checked when the
+    /// geometry is quantized.
+    pub moved: Vec<usize>,
+}
+
+impl Verification {
+    pub fn is_ok(&self) -> bool {
+        self.mismatched.is_empty()
+            && self.flipped.is_empty()
+            && self.lost.is_empty()
+            && self.duplicated.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+// Rotation of `f` that starts with its smallest index
+fn canonical(f: [usize; 3]) -> [usize; 3] {
+    let r = (0..3).min_by_key(|&r| f[r]).unwrap();
+    [f[r], f[(r + 1) % 3], f[(r + 2) % 3]]
+}
+
+/// Compress `mesh` with `options`, decompress it again and compare the faces
+/// after mapping the decoded vertices back to the input ones. Welded vertices
+/// that are not restored are compared as the vertex they were welded into.
+pub fn verify(mesh: &Mesh, options: &CompressOptions) -> Result<Verification, EdgeBreakerError> {
+    let edgebreaker::Encoded {
+        compressed,
+        order,
+        welds,
+        ..
+    } = edgebreaker::encode_mesh(mesh, options)?;
+    let faces = match options.restore_welded || welds.is_empty() {
+        true => mesh.faces.clone(),
+        false => weld::remap_faces(&mesh.faces, &welds),
+    };
+    let decoded = decompress(&compressed)?;
+
+    let mut report = Verification {
+        input_faces: mesh.faces.len(),
+        decoded_faces: decoded.faces.len(),
+        ..Default::default()
+    };
+
+    let mut remaining: HashMap<[usize; 3], usize> = HashMap::new();
+    for &f in &faces {
+        *remaining.entry(canonical(f)).or_default() += 1;
+    }
+
+    let mut unmatched = Vec::new();
+    for f in &decoded.faces {
+        let f = canonical(f.map(|v| order[v]));
+        match remaining.get_mut(&f) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => unmatched.push(f),
+        }
+    }
+
+    for f in unmatched {
+        let reversed = canonical([f[0], f[2], f[1]]);
+        if let Some(count) = remaining.get_mut(&reversed)
+            && *count > 0
+        {
+            *count -= 1;
+            report.flipped.push(f);
+        } else if remaining.contains_key(&f) {
+            report.duplicated.push(f);
+        } else {
+            report.mismatched.push(f);
+        }
+    }
+
+    for &f in &faces {
+        let f = canonical(f);
+        if let Some(count) = remaining.get_mut(&f)
+            && *count > 0
+        {
+            *count -= 1;
+            report.lost.push(f);
+        }
+    }
+
+    if compressed.geometry.is_none() {
+        report.moved = decoded
+            .vertices
+            .iter()
+            .zip(&order)
+            .filter(|&(v, &o)| *v != mesh.vertices[o])
+            .map(|(_, &o)| o)
+            .collect();
+    }
+
+    Ok(report)
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "faces: {} input, {} decoded",
+            self.input_faces, self.decoded_faces
+        )?;
+        for (name, faces) in [
+            ("mismatched", &self.mismatched),
+            ("flipped", &self.flipped),
+            ("lost", &self.lost),
+            ("duplicated", &self.duplicated),
+        ] {
+            write!(f, "{name}: {}", faces.len())?;
+            for face in faces.iter().take(10) {
+                write!(f, " {face:?}")?;
+            }
+            if faces.len() > 10 {
+                write!(f, " ...")?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "moved vertices: {}", self.moved.len())?;
+        write!(f, "{}", if self.is_ok() { "OK" } else { "FAILED" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_grid() {
+        let mut mesh = Mesh::default();
+        for i in 0..9 {
+            mesh.vertices.push([(i % 3) as f32, (i / 3) as f32, 0.0]);
+        }
+        for j in 0..2 {
+            for i in 0..2 {
+                let a = j * 3 + i;
+                mesh.faces.push([a, a + 1, a + 4]);
+                mesh.faces.push([a, a + 4, a + 3]);
+            }
+        }
+
+        let report = verify(&mesh, &CompressOptions::default()).unwrap();
+        assert!(report.is_ok(), "{report}");
+        assert_eq!(report.decoded_faces, 8);
+    }
+}
@@ -0,0 +1,117 @@
+// ,---------------------------------------------------------------------------
+// | Weld: merge coincident vertices
+// '---------------------------------------------------------------------------
+
+use std::collections::HashMap;
+
+/// Merge vertices that are closer than `tolerance` to each other. Returns the
+/// remaining vertices and, for every input vertex, its index among them.
+///
+/// With a tolerance of zero only bitwise equal positions are merged (`-0.0`
+/// and `0.0` are considered equal). Otherwise vertices are bucketed into a
+/// spatial hash with cells of size `tolerance` and each vertex is merged into
+/// the first earlier vertex within that distance.
+pub fn weld(vertices: &[[f32; 3]], tolerance: f32) -> (Vec<[f32; 3]>, Vec<usize>) {
+    let mut welded = Vec::new();
+    let mut remap = Vec::with_capacity(vertices.len());
+
+    if tolerance <= 0.0 {
+        let mut seen: HashMap<[u32; 3], usize> = HashMap::new();
+        for v in vertices {
+            let key = v.map(|c| (c + 0.0).to_bits());
+            let i = *seen.entry(key).or_insert_with(|| {
+                welded.push(*v);
+                welded.len() - 1
+            });
+            remap.push(i);
+        }
+        return (welded, remap);
+    }
+
+    let cell = |v: &[f32; 3]| v.map(|c| (c / tolerance).floor() as i64);
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    for v in vertices {
+        let [x, y, z] = cell(v);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(bucket) = grid.get(&[x + dx, y + dy, z + dz]) else {
+                        continue;
+                    };
+                    if let Some(&i) = bucket.iter().find(|&&i| {
+                        let d = (0..3).map(|k| (welded[i][k] - v[k]).powi(2)).sum::<f32>();
+                        d <= tolerance * tolerance
+                    }) {
+                        found = Some(i);
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let i = found.unwrap_or_else(|| {
+            welded.push(*v);
+            grid.entry([x, y, z]).or_default().push(welded.len() - 1);
+            welded.len() - 1
+        });
+        remap.push(i);
+    }
+
+    (welded, remap)
+}
+
+/// Like [`weld`], but keeps the vertices in place and returns for every
+/// vertex the first one it was merged with.
+pub fn representatives(vertices: &[[f32; 3]], tolerance: f32) -> Vec<usize> {
+    let (welded, remap) = weld(vertices, tolerance);
+    let mut first = vec![usize::MAX; welded.len()];
+    for (p, &w) in remap.iter().enumerate().rev() {
+        first[w] = p;
+    }
+    remap.iter().map(|&w| first[w]).collect()
+}
+
+/// Point `faces` at the `representatives` of their vertices. Faces that
+/// would collapse keep their own vertices.
+pub fn remap_faces(faces: &[[usize; 3]], representatives: &[usize]) -> Vec<[usize; 3]> {
+    faces
+        .iter()
+        .map(|f| {
+            let [a, b, c] = f.map(|v| representatives[v]);
+            if a != b && b != c && c != a {
+                [a, b, c]
+            } else {
+                *f
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weld_exact_and_tolerance() {
+        let vertices = [
+            [0.0, 0.0, 0.0],
+            [-0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.001, 0.0, 0.0],
+        ];
+
+        let (welded, remap) = weld(&vertices, 0.0);
+        assert_eq!(welded.len(), 3);
+        assert_eq!(remap, vec![0, 0, 1, 2]);
+
+        let (welded, remap) = weld(&vertices, 0.01);
+        assert_eq!(welded.len(), 2);
+        assert_eq!(remap, vec![0, 0, 1, 1]);
+
+        let representatives = representatives(&vertices, 0.01);
+        assert_eq!(representatives, vec![0, 0, 2, 2]);
+        let faces = remap_faces(&[[1, 3, 0], [0, 3, 4]], &[0, 0, 2, 2, 4]);
+        assert_eq!(faces, vec![[1, 3, 0], [0, 2, 4]]);
+    }
+}